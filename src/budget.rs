@@ -0,0 +1,143 @@
+use crate::tokenize;
+
+/// Limits on the links inserted into a markdown. A limit of 0 means there is
+/// none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    /// links in a single paragraph
+    pub per_paragraph: usize,
+    /// links in the whole markdown
+    pub per_document: usize,
+    /// links to the same target
+    pub per_target: usize,
+    /// the words that must separate two links in a paragraph
+    pub min_word_gap: usize,
+    /// whether `per_target` counts again from every heading
+    pub relink_sections: bool,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self {
+            per_paragraph: 4,
+            per_document: 0,
+            per_target: 1,
+            min_word_gap: 0,
+            relink_sections: false,
+        }
+    }
+}
+
+/// The limits a markdown sets for itself in its front matter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Overrides {
+    pub per_paragraph: Option<usize>,
+    pub per_document: Option<usize>,
+    pub per_target: Option<usize>,
+    pub min_word_gap: Option<usize>,
+    pub relink_sections: Option<bool>,
+}
+
+impl Budget {
+    /// This budget with the limits the markdown sets for itself.
+    pub fn with(&self, overrides: &Overrides) -> Self {
+        Self {
+            per_paragraph: overrides.per_paragraph.unwrap_or(self.per_paragraph),
+            per_document: overrides.per_document.unwrap_or(self.per_document),
+            per_target: overrides.per_target.unwrap_or(self.per_target),
+            min_word_gap: overrides.min_word_gap.unwrap_or(self.min_word_gap),
+            relink_sections: overrides.relink_sections.unwrap_or(self.relink_sections),
+        }
+    }
+
+    /// How many links the next paragraph may get, given the links already
+    /// in the markdown.
+    pub fn paragraph_room(&self, in_document: usize) -> usize {
+        let paragraph = match self.per_paragraph {
+            0 => usize::MAX,
+            limit => limit,
+        };
+        match self.per_document {
+            0 => paragraph,
+            limit => paragraph.min(limit.saturating_sub(in_document)),
+        }
+    }
+
+    /// Whether another link to a target that already has `count` fits.
+    pub fn allows_target(&self, count: usize) -> bool {
+        self.per_target == 0 || count < self.per_target
+    }
+
+    /// Whether two links, given as byte ranges of the paragraph, are far
+    /// enough apart.
+    pub fn allows_gap(&self, paragraph: &str, a: (usize, usize), b: (usize, usize)) -> bool {
+        if self.min_word_gap == 0 {
+            return true;
+        }
+        let (first, second) = if a.0 <= b.0 { (a, b) } else { (b, a) };
+        if first.1 > second.0 {
+            return false;
+        }
+        let words = tokenize::tokenize(&paragraph[first.1..second.0])
+            .filter(|(_, token)| token.chars().any(char::is_alphanumeric))
+            .count();
+        words >= self.min_word_gap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paragraph_room() {
+        let budget = Budget::default();
+        assert_eq!(budget.paragraph_room(100), 4);
+
+        let budget = Budget {
+            per_document: 6,
+            ..Default::default()
+        };
+        assert_eq!(budget.paragraph_room(0), 4);
+        assert_eq!(budget.paragraph_room(4), 2);
+        assert_eq!(budget.paragraph_room(7), 0);
+
+        let budget = Budget {
+            per_paragraph: 0,
+            ..Default::default()
+        };
+        assert_eq!(budget.paragraph_room(100), usize::MAX);
+    }
+
+    #[test]
+    fn test_overrides() {
+        let budget = Budget::default().with(&Overrides {
+            per_target: Some(2),
+            relink_sections: Some(true),
+            ..Default::default()
+        });
+        assert_eq!(
+            budget,
+            Budget {
+                per_target: 2,
+                relink_sections: true,
+                ..Default::default()
+            }
+        );
+        assert!(budget.allows_target(1));
+        assert!(!budget.allows_target(2));
+    }
+
+    #[test]
+    fn test_allows_gap() {
+        let paragraph = "alpha, beta gamma delta";
+        let budget = Budget {
+            min_word_gap: 2,
+            ..Default::default()
+        };
+        assert!(!budget.allows_gap(paragraph, (0, 5), (7, 11)));
+        assert!(!budget.allows_gap(paragraph, (12, 17), (0, 5)));
+        assert!(budget.allows_gap(paragraph, (0, 5), (18, 23)));
+        assert!(Budget::default().allows_gap(paragraph, (0, 5), (7, 11)));
+    }
+}
@@ -0,0 +1,131 @@
+use std::path::{Path, PathBuf};
+
+/// the config file picked up from the current folder
+pub const FILE_NAME: &str = "mdlinker.toml";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("cannot read config {0}: {1}")]
+    Read(PathBuf, std::io::Error),
+    #[error("invalid config {0}: {1}")]
+    Invalid(PathBuf, toml::de::Error),
+}
+
+/// The options of a project, read from `mdlinker.toml`. Every key mirrors
+/// the command line flag of the same name, which takes precedence over it.
+#[derive(Debug, Default, Clone, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub glob: Option<String>,
+    pub output: Option<PathBuf>,
+    pub in_place: bool,
+    pub backup_suffix: Option<String>,
+    pub backup_dir: Option<PathBuf>,
+    pub seed: Option<u64>,
+    pub url_template: Option<String>,
+    pub base_url: Option<String>,
+    pub link_in: Option<Vec<mdlinker::write::Container>>,
+    pub extensions: Option<Vec<mdlinker::write::Extension>>,
+    pub reformat: bool,
+    pub slug_from: Option<mdlinker::document::SlugStrategy>,
+    pub slug_field: Option<String>,
+    pub keyword_fields: Option<Vec<String>>,
+    pub marker: Option<mdlinker::write::Marker>,
+    pub max_links_per_paragraph: Option<usize>,
+    pub max_links_per_document: Option<usize>,
+    pub max_links_per_target: Option<usize>,
+    pub min_word_gap: Option<usize>,
+    pub relink_sections: bool,
+}
+
+impl Config {
+    /// Loads the config at the path, or `mdlinker.toml` in the current folder
+    /// if no path is given. A missing `mdlinker.toml` is an empty config.
+    pub fn load(path: Option<&Path>) -> Result<Self, Error> {
+        let (path, required) = match path {
+            Some(path) => (path, true),
+            None => (Path::new(FILE_NAME), false),
+        };
+
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if !required && err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Self::default())
+            }
+            Err(err) => return Err(Error::Read(path.to_owned(), err)),
+        };
+        log::info!("using config {}", path.to_string_lossy());
+
+        toml::from_str(&content).map_err(|err| Error::Invalid(path.to_owned(), err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mdlinker::document::SlugStrategy;
+    use mdlinker::write::{Container, Extension, Marker};
+
+    #[test]
+    fn test_config_parse() {
+        let config: Config = toml::from_str(
+            r#"
+glob = "docs/**/*.md"
+in-place = true
+backup-suffix = ".bak"
+url-template = "{base}/{slug}/"
+base-url = "https://example.com"
+link-in = ["list", "block-quote"]
+extensions = ["gfm", "superscript"]
+slug-from = "directory"
+slug-field = "url"
+keyword-fields = ["tags", "title"]
+marker = "comment"
+max-links-per-paragraph = 2
+relink-sections = true
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            Config {
+                glob: Some("docs/**/*.md".to_string()),
+                in_place: true,
+                backup_suffix: Some(".bak".to_string()),
+                url_template: Some("{base}/{slug}/".to_string()),
+                base_url: Some("https://example.com".to_string()),
+                link_in: Some(vec![Container::List, Container::BlockQuote]),
+                extensions: Some(vec![Extension::Gfm, Extension::Superscript]),
+                slug_from: Some(SlugStrategy::Directory),
+                slug_field: Some("url".to_string()),
+                keyword_fields: Some(vec!["tags".to_string(), "title".to_string()]),
+                marker: Some(Marker::Comment),
+                max_links_per_paragraph: Some(2),
+                relink_sections: true,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_config_empty() {
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn test_config_unknown_key() {
+        let err = toml::from_str::<Config>("url_template = \"{slug}\"").unwrap_err();
+        assert!(err.to_string().contains("unknown field `url_template`"));
+    }
+
+    #[test]
+    fn test_config_load_missing() {
+        let path = Path::new("does-not-exist.toml");
+        assert!(matches!(
+            Config::load(Some(path)),
+            Err(Error::Read(p, _)) if p == path
+        ));
+    }
+}
@@ -1,44 +1,366 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::budget::Overrides;
+use crate::front_matter::Dialect;
+pub use crate::slug::{SlugStrategy, Slugger};
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("missing front matter")]
     MissingFrontMatter,
-    #[error("front matter does not have slug or keywords list: {0}")]
-    FrontMatterMismatch(serde_yaml::Error),
+    #[error("front matter cannot be read: {0}")]
+    FrontMatterMismatch(Box<dyn std::error::Error + Send + Sync>),
+    #[error("no slug can be derived from {0}")]
+    MissingSlug(PathBuf),
+}
+
+/// Which front matter fields the slug and the keywords of a markdown are
+/// read from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fields {
+    /// the field holding the slug
+    pub slug: String,
+    /// the fields whose values are merged into the keywords
+    pub keywords: Vec<String>,
+}
+
+impl Default for Fields {
+    fn default() -> Self {
+        Self {
+            slug: "slug".to_string(),
+            keywords: ["keywords", "title", "aliases"].map(String::from).to_vec(),
+        }
+    }
+}
+
+type Value = serde_yaml::Value;
+
+/// The front matter of a markdown. Fields mdlinker does not know about are
+/// kept in `extra`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrontMatter {
+    /// derived from the path of the markdown if missing
+    pub slug: Option<String>,
+    /// the terms other markdowns are linked to this one by, merged from all
+    /// keyword fields, or `None` if the front matter has none of them
+    pub keywords: Option<Vec<String>>,
+    pub title: Option<String>,
+    pub aliases: Vec<String>,
+    /// drafts are never linked to
+    pub draft: bool,
+    /// whether links may be inserted into this page
+    pub autolink: bool,
+    /// whether other pages may link to this page
+    pub link_target: bool,
+    /// the link limits this page sets for itself
+    pub budget: Overrides,
+    pub extra: BTreeMap<String, Value>,
+}
+
+fn mismatch(field: &str, expected: &str) -> Error {
+    Error::FrontMatterMismatch(format!("{field} is not {expected}").into())
+}
+
+/// The text of a string, number or boolean.
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
 }
 
-#[derive(Debug, serde::Deserialize, Clone)]
-pub struct FrontMatter<'a> {
-    pub slug: &'a str,
-    pub keywords: Vec<&'a str>,
+/// A boolean, or `default` if the field is missing.
+fn flag(extra: &BTreeMap<String, Value>, field: &str, default: bool) -> Result<bool, Error> {
+    match extra.get(field) {
+        Some(value) => value.as_bool().ok_or_else(|| mismatch(field, "a boolean")),
+        None => Ok(default),
+    }
+}
+
+/// A non-negative number, if the field is there.
+fn count(extra: &BTreeMap<String, Value>, field: &str) -> Result<Option<usize>, Error> {
+    match extra.get(field) {
+        Some(value) => match value.as_u64() {
+            Some(count) => Ok(Some(count as usize)),
+            None => Err(mismatch(field, "a non-negative number")),
+        },
+        None => Ok(None),
+    }
 }
 
-impl<'a> FrontMatter<'a> {
-    fn parse(content: &'a str) -> Result<Self, Error> {
-        let parsed_fm: FrontMatter =
-            serde_yaml::from_str(content).map_err(Error::FrontMatterMismatch)?;
-        Ok(parsed_fm)
+/// A single scalar or a list of them.
+fn scalars(field: &str, value: &Value) -> Result<Vec<String>, Error> {
+    match value {
+        Value::Sequence(values) => values
+            .iter()
+            .map(|value| scalar(value).ok_or_else(|| mismatch(field, "a list of strings")))
+            .collect(),
+        Value::Null => Ok(vec![]),
+        value => Ok(vec![
+            scalar(value).ok_or_else(|| mismatch(field, "a string or a list"))?
+        ]),
+    }
+}
+
+impl FrontMatter {
+    fn parse(content: &str, dialect: Dialect, fields: &Fields) -> Result<Self, Error> {
+        let parsed: Option<BTreeMap<String, Value>> =
+            match dialect {
+                Dialect::Yaml => serde_yaml::from_str(content)
+                    .map_err(|e| Error::FrontMatterMismatch(e.into()))?,
+                Dialect::Toml => {
+                    toml::from_str(content).map_err(|e| Error::FrontMatterMismatch(e.into()))?
+                }
+                Dialect::Json => serde_json::from_str(content)
+                    .map_err(|e| Error::FrontMatterMismatch(e.into()))?,
+            };
+        let mut extra = parsed.unwrap_or_default();
+
+        let slug = match extra.get(&fields.slug) {
+            Some(value) => Some(scalar(value).ok_or_else(|| mismatch(&fields.slug, "a string"))?),
+            None => None,
+        };
+        let mut keywords: Option<Vec<String>> = None;
+        for field in &fields.keywords {
+            if let Some(value) = extra.get(field) {
+                keywords
+                    .get_or_insert_with(Vec::new)
+                    .extend(scalars(field, value)?);
+            }
+        }
+        let title = match extra.get("title") {
+            Some(value) => Some(scalar(value).ok_or_else(|| mismatch("title", "a string"))?),
+            None => None,
+        };
+        let aliases = match extra.get("aliases") {
+            Some(value) => scalars("aliases", value)?,
+            None => vec![],
+        };
+        let draft = flag(&extra, "draft", false)?;
+        let autolink = flag(&extra, "autolink", true)?;
+        let link_target = flag(&extra, "link_target", true)?;
+        let budget = Overrides {
+            per_paragraph: count(&extra, "max_links_per_paragraph")?,
+            per_document: count(&extra, "max_links_per_document")?,
+            per_target: count(&extra, "max_links_per_target")?,
+            min_word_gap: count(&extra, "min_word_gap")?,
+            relink_sections: match extra.get("relink_sections") {
+                Some(_) => Some(flag(&extra, "relink_sections", false)?),
+                None => None,
+            },
+        };
+
+        let known = [
+            &fields.slug,
+            "title",
+            "aliases",
+            "draft",
+            "autolink",
+            "link_target",
+            "max_links_per_paragraph",
+            "max_links_per_document",
+            "max_links_per_target",
+            "min_word_gap",
+            "relink_sections",
+        ];
+        extra.retain(|key, _| !known.contains(&key.as_str()) && !fields.keywords.contains(key));
+        Ok(Self {
+            slug,
+            keywords,
+            title,
+            aliases,
+            draft,
+            autolink,
+            link_target,
+            budget,
+            extra,
+        })
     }
 }
 
-#[derive(Debug, serde::Deserialize, Clone)]
+/// How front matter is read: which fields hold the slug and the keywords,
+/// and how a missing slug is derived.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schema {
+    pub fields: Fields,
+    pub slugs: Slugger,
+}
+
+#[derive(Debug, Clone)]
 pub struct Document<'a> {
-    pub front_matter: FrontMatter<'a>,
-    pub front_matter_all: &'a str,
+    pub front_matter: FrontMatter,
+    /// the slug of the front matter, or the one derived from the path
+    pub slug: String,
+    /// everything before the markdown, delimiters and byte order mark included
+    pub header: &'a str,
     pub document: &'a str,
 }
 
 impl<'a> Document<'a> {
-    pub fn parse(content: &'a str) -> Result<Self, Error> {
-        let (fm, md) = match crate::front_matter::split_front_matter(content) {
+    pub fn parse(content: &'a str, path: &Path, schema: &Schema) -> Result<Self, Error> {
+        let (dialect, fm, md) = match crate::front_matter::split_front_matter(content) {
             Some(fm_md) => fm_md,
             None => return Err(Error::MissingFrontMatter),
         };
-        let front_matter = FrontMatter::parse(fm)?;
+        let front_matter = FrontMatter::parse(fm, dialect, &schema.fields)?;
+        let slug = match &front_matter.slug {
+            Some(slug) => slug.clone(),
+            None => schema
+                .slugs
+                .derive(path)
+                .ok_or_else(|| Error::MissingSlug(path.to_owned()))?,
+        };
 
         Ok(Self {
             front_matter,
-            front_matter_all: fm,
+            slug,
+            header: &content[..content.len() - md.len()],
             document: md,
         })
     }
+
+    /// The terms this page is linked by: its keywords, or its slug if it has
+    /// none of the keyword fields.
+    pub fn terms(&self) -> Vec<&str> {
+        match &self.front_matter.keywords {
+            Some(keywords) => keywords.iter().map(String::as_str).collect(),
+            None => vec![self.slug.as_str()],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slug::SlugStrategy;
+
+    fn parse(content: &str) -> Result<Document<'_>, Error> {
+        Document::parse(content, Path::new("docs/guide.md"), &Schema::default())
+    }
+
+    #[test]
+    fn test_parse_dialects() {
+        for content in [
+            "---\nslug: guide\nkeywords: [a, \"b\"]\n---\ncontent",
+            "+++\nslug = \"guide\"\nkeywords = [\"a\", \"b\"]\n+++\ncontent",
+            "{\"slug\": \"guide\", \"keywords\": [\"a\", \"b\"]}\ncontent",
+        ] {
+            let doc = parse(content).unwrap();
+            assert_eq!(doc.slug, "guide");
+            assert_eq!(doc.terms(), vec!["a", "b"]);
+            assert_eq!(doc.document, "content");
+            assert_eq!(doc.header.len() + doc.document.len(), content.len());
+        }
+    }
+
+    #[test]
+    fn test_parse_mismatch() {
+        let content = "+++\nslug: guide\n+++\ncontent";
+        assert!(matches!(parse(content), Err(Error::FrontMatterMismatch(_))));
+    }
+
+    #[test]
+    fn test_parse_optional_fields() {
+        let content = "---
+slug: cpp
+title: \"C++ \\u2014 a primer\"
+aliases: ['it''s C++', \"cplusplus\"]
+draft: true
+weight: 3
+---
+content";
+        let doc = parse(content).unwrap();
+        let fm = &doc.front_matter;
+        assert_eq!(fm.title.as_deref(), Some("C++ \u{2014} a primer"));
+        assert!(fm.draft);
+        assert_eq!(fm.extra["weight"], serde_yaml::Value::from(3));
+        assert_eq!(fm.extra.len(), 1);
+        assert_eq!(
+            doc.terms(),
+            vec!["C++ \u{2014} a primer", "it's C++", "cplusplus"]
+        );
+
+        let doc = parse("---\n---\n").unwrap();
+        assert_eq!(doc.terms(), vec!["guide"]);
+
+        let doc = parse("---\nslug: cpp\nweight: 3\n---\n").unwrap();
+        assert_eq!(doc.front_matter.keywords, None);
+        assert_eq!(doc.terms(), vec!["cpp"]);
+
+        let doc = parse("+++\nslug = \"a\"\nkeywords = []\n+++\n").unwrap();
+        assert!(!doc.front_matter.draft);
+        assert!(doc.front_matter.autolink);
+        assert!(doc.front_matter.link_target);
+        assert!(doc.terms().is_empty());
+
+        let doc = parse("---\nautolink: false\nlink_target: false\n---\n").unwrap();
+        assert!(!doc.front_matter.autolink);
+        assert!(!doc.front_matter.link_target);
+        assert!(doc.front_matter.extra.is_empty());
+        assert!(matches!(
+            parse("---\nautolink: no way\n---\n"),
+            Err(Error::FrontMatterMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_derives_slug() {
+        let content = "---\nkeywords: [a]\n---\ncontent";
+        assert_eq!(parse(content).unwrap().slug, "guide");
+
+        let schema = Schema {
+            slugs: Slugger::new(SlugStrategy::Directory, "docs/**/*.md"),
+            ..Default::default()
+        };
+        let doc = Document::parse(content, Path::new("docs/api/index.md"), &schema).unwrap();
+        assert_eq!(doc.front_matter.slug, None);
+        assert_eq!(doc.slug, "api");
+    }
+
+    #[test]
+    fn test_parse_custom_fields() {
+        let schema = Schema {
+            fields: Fields {
+                slug: "url".to_string(),
+                keywords: vec!["tags".to_string(), "title".to_string()],
+            },
+            ..Default::default()
+        };
+        let content = "---\nurl: guide\ntags: [a, 2024]\ntitle: The Guide\nkeywords: [c]\n---\n";
+        let doc = Document::parse(content, Path::new("page.md"), &schema).unwrap();
+        assert_eq!(doc.slug, "guide");
+        assert_eq!(doc.terms(), vec!["a", "2024", "The Guide"]);
+        assert_eq!(doc.front_matter.title.as_deref(), Some("The Guide"));
+        assert_eq!(
+            doc.front_matter.extra.keys().collect::<Vec<_>>(),
+            ["keywords"]
+        );
+
+        let content = "---\nurl: guide\ntags: {a: b}\n---\n";
+        assert!(matches!(
+            Document::parse(content, Path::new("page.md"), &schema),
+            Err(Error::FrontMatterMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_budget() {
+        let doc = parse("---\nmax_links_per_paragraph: 2\nrelink_sections: true\n---\n").unwrap();
+        assert_eq!(
+            doc.front_matter.budget,
+            Overrides {
+                per_paragraph: Some(2),
+                relink_sections: Some(true),
+                ..Default::default()
+            }
+        );
+        assert!(doc.front_matter.extra.is_empty());
+        assert!(matches!(
+            parse("---\nmin_word_gap: -1\n---\n"),
+            Err(Error::FrontMatterMismatch(_))
+        ));
+    }
 }
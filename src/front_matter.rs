@@ -1,13 +1,78 @@
-pub fn split_front_matter(input: &str) -> Option<(&str, &str)> {
-    let ("---", next) = input.trim_start().split_at("---".len()) else {
-        return None;
-    };
+/// The format of a front matter block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    /// YAML between `---` lines
+    Yaml,
+    /// TOML between `+++` lines
+    Toml,
+    /// a JSON object at the very start of the file
+    Json,
+}
 
-    let Some((front_matter, markdown)) = next.split_once("\n---\n") else {
+impl Dialect {
+    /// the line delimiting the front matter, if the dialect has one
+    pub fn delimiter(&self) -> Option<&'static str> {
+        match self {
+            Dialect::Yaml => Some("---"),
+            Dialect::Toml => Some("+++"),
+            Dialect::Json => None,
+        }
+    }
+}
+
+const BYTE_ORDER_MARK: char = '\u{feff}';
+
+fn split_json(input: &str) -> Option<(Dialect, &str, &str)> {
+    let mut objects =
+        serde_json::Deserializer::from_str(input).into_iter::<serde::de::IgnoredAny>();
+    objects.next()?.ok()?;
+    let (front_matter, markdown) = input.split_at(objects.byte_offset());
+    let markdown = markdown
+        .strip_prefix("\r\n")
+        .or_else(|| markdown.strip_prefix('\n'))
+        .unwrap_or(markdown);
+    Some((Dialect::Json, front_matter, markdown))
+}
+
+// whether a line, with or without its line ending, is the delimiter
+fn is_delimiter(line: &str, delimiter: &str) -> bool {
+    line.trim_end() == delimiter
+}
+
+/// Scans the input line by line for a front matter block between two
+/// delimiter lines. Lines may end in LF or CRLF, the closing delimiter may be
+/// the last line of the input, and a leading byte order mark is skipped.
+fn split_delimited(input: &str, delimiter: &str) -> Option<(usize, usize, usize)> {
+    let mut lines = input.split_inclusive('\n');
+    let first = lines.next()?;
+    if !is_delimiter(first, delimiter) {
         return None;
-    };
+    }
 
-    Some((front_matter, markdown))
+    let start = first.len();
+    let mut offset = start;
+    for line in lines {
+        if is_delimiter(line, delimiter) {
+            return Some((start, offset, offset + line.len()));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+pub fn split_front_matter(input: &str) -> Option<(Dialect, &str, &str)> {
+    let input = input.trim_start_matches(BYTE_ORDER_MARK).trim_start();
+    if input.starts_with('{') {
+        return split_json(input);
+    }
+
+    for dialect in [Dialect::Yaml, Dialect::Toml] {
+        let delimiter = dialect.delimiter()?;
+        if let Some((start, end, markdown)) = split_delimited(input, delimiter) {
+            return Some((dialect, &input[start..end], &input[markdown..]));
+        }
+    }
+    None
 }
 
 #[cfg(test)]
@@ -25,8 +90,9 @@ description: A sample description
 
 This is the main content.";
 
-        let (front_matter, markdown) = split_front_matter(input).unwrap();
+        let (dialect, front_matter, markdown) = split_front_matter(input).unwrap();
 
+        assert_eq!(dialect, Dialect::Yaml);
         assert_eq!(
             front_matter.trim(),
             "title: A sample title\ndescription: A sample description\n---duplicate"
@@ -50,12 +116,85 @@ This is the main content.";
 
 This is the main content with empty front matter.";
 
-        let (front_matter, markdown) = split_front_matter(input).unwrap();
+        let (dialect, front_matter, markdown) = split_front_matter(input).unwrap();
 
+        assert_eq!(dialect, Dialect::Yaml);
         assert_eq!(front_matter, "");
         assert_eq!(
             markdown.trim(),
             "This is the main content with empty front matter."
         );
     }
+
+    #[test]
+    fn test_split_front_matter_toml() {
+        let input = "+++
+title = \"A sample title\"
++++
+
+This is the main content.";
+
+        let (dialect, front_matter, markdown) = split_front_matter(input).unwrap();
+
+        assert_eq!(dialect, Dialect::Toml);
+        assert_eq!(front_matter.trim(), "title = \"A sample title\"");
+        assert_eq!(markdown.trim(), "This is the main content.");
+    }
+
+    #[test]
+    fn test_split_front_matter_json() {
+        let input = "{
+    \"title\": \"A {sample} title\",
+    \"keywords\": [\"a\", \"b\"]
+}
+
+This is the main content.";
+
+        let (dialect, front_matter, markdown) = split_front_matter(input).unwrap();
+
+        assert_eq!(dialect, Dialect::Json);
+        assert!(front_matter.starts_with('{'));
+        assert!(front_matter.ends_with('}'));
+        assert_eq!(markdown, "\nThis is the main content.");
+    }
+
+    #[test]
+    fn test_split_front_matter_unclosed() {
+        assert!(split_front_matter("+++\ntitle = 1\n---\ncontent").is_none());
+        assert!(split_front_matter("{ \"title\": 1\ncontent").is_none());
+        assert!(split_front_matter("--").is_none());
+    }
+
+    #[test]
+    fn test_split_front_matter_crlf() {
+        let input = "---\r\ntitle: A sample title\r\n---\r\n\r\nThis is the main content.\r\n";
+
+        let (dialect, front_matter, markdown) = split_front_matter(input).unwrap();
+
+        assert_eq!(dialect, Dialect::Yaml);
+        assert_eq!(front_matter, "title: A sample title\r\n");
+        assert_eq!(markdown, "\r\nThis is the main content.\r\n");
+    }
+
+    #[test]
+    fn test_split_front_matter_bom() {
+        let input = "\u{feff}+++\ntitle = 1\n+++\ncontent";
+
+        let (dialect, front_matter, markdown) = split_front_matter(input).unwrap();
+
+        assert_eq!(dialect, Dialect::Toml);
+        assert_eq!(front_matter, "title = 1\n");
+        assert_eq!(markdown, "content");
+    }
+
+    #[test]
+    fn test_split_front_matter_closing_at_eof() {
+        let (_, front_matter, markdown) = split_front_matter("---\ntitle: 1\n---").unwrap();
+        assert_eq!(front_matter, "title: 1\n");
+        assert_eq!(markdown, "");
+
+        let (_, front_matter, markdown) = split_front_matter("---  \ntitle: 1\n--- \r\n").unwrap();
+        assert_eq!(front_matter, "title: 1\n");
+        assert_eq!(markdown, "");
+    }
 }
@@ -1,8 +1,13 @@
 use std::io::Read;
+use std::path::Path;
 
 use crate::{document, keyword};
 
-pub fn index(keywords: &mut keyword::Keywords, glob_str: &str) -> anyhow::Result<()> {
+pub fn index(
+    keywords: &mut keyword::Keywords,
+    glob_str: &str,
+    schema: &document::Schema,
+) -> anyhow::Result<()> {
     // Glob for markdown files
     for entry in glob::glob(glob_str)? {
         let path = entry?;
@@ -10,7 +15,7 @@ pub fn index(keywords: &mut keyword::Keywords, glob_str: &str) -> anyhow::Result
         let mut file = std::fs::File::open(&path)?;
         let mut content = String::new();
         file.read_to_string(&mut content)?;
-        if let Err(err) = index_content(keywords, &content) {
+        if let Err(err) = index_content(keywords, &path, &content, schema) {
             log::warn!(
                 "file {} cannot not be indexed: {}",
                 &path.to_string_lossy(),
@@ -21,8 +26,29 @@ pub fn index(keywords: &mut keyword::Keywords, glob_str: &str) -> anyhow::Result
     Ok(())
 }
 
-fn index_content(keywords: &mut keyword::Keywords, content: &str) -> Result<(), document::Error> {
-    let doc = document::Document::parse(content)?;
-    keywords.insert(doc.front_matter.slug, &doc.front_matter.keywords);
+fn index_content(
+    keywords: &mut keyword::Keywords,
+    path: &Path,
+    content: &str,
+    schema: &document::Schema,
+) -> Result<(), document::Error> {
+    let doc = document::Document::parse(content, path, schema)?;
+    if doc.front_matter.draft {
+        log::info!(
+            "{} is a draft, so it is not linked to",
+            path.to_string_lossy()
+        );
+        return Ok(());
+    }
+    if !doc.front_matter.link_target {
+        log::info!(
+            "{} is not a link target, so it is not linked to",
+            path.to_string_lossy()
+        );
+        return Ok(());
+    }
+    keywords.insert(&doc.slug, &doc.terms());
+    keywords.set_path(&doc.slug, path);
+    keywords.set_words(&doc.slug, doc.document);
     Ok(())
 }
@@ -1,33 +1,183 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+use crate::tokenize::tokenize;
 
 type Slug = String;
 type Keyword = String;
 
 #[derive(Debug, Default, Clone, PartialEq)]
 pub struct Keywords {
-    pub(crate) map: HashMap<Keyword, HashSet<Slug>>,
+    pub(crate) map: HashMap<Keyword, BTreeSet<Slug>>,
+    /// the keywords of each slug
+    keywords: HashMap<Slug, BTreeSet<Keyword>>,
+    /// the markdown each slug was indexed from
+    paths: HashMap<Slug, PathBuf>,
+    /// the slug of each indexed markdown, by its path without the extension
+    slugs_by_path: HashMap<PathBuf, Slug>,
+    /// the vocabulary of the markdown each slug was indexed from
+    words: HashMap<Slug, HashSet<String>>,
+    /// every keyword compiled into a word trie so a paragraph can be
+    /// matched against all of them in one pass
+    trie: TrieNode,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct TrieNode {
+    children: HashMap<String, TrieNode>,
+    /// set when the path from the root to this node spells out a keyword
+    keyword: Option<Keyword>,
+}
+
+/// the path without `.` folders and the file extension
+fn path_key(path: &Path) -> PathBuf {
+    path.with_extension("")
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect()
+}
+
+/// A keyword occurrence, given as a byte range into the matched text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match<'a> {
+    pub start: usize,
+    pub end: usize,
+    pub keyword: &'a str,
 }
 
 impl Keywords {
     pub fn new() -> Self {
         Self {
             map: Default::default(),
+            keywords: Default::default(),
+            paths: Default::default(),
+            slugs_by_path: Default::default(),
+            words: Default::default(),
+            trie: Default::default(),
         }
     }
 
     pub fn insert<K: AsRef<str>>(&mut self, slug: &str, keywords: &[K]) {
-        let slug = slug.trim();
+        let slug = normalize_slug(slug);
         for keyword in keywords {
-            let keyword = keyword.as_ref().to_lowercase();
+            let keyword = normalize(keyword.as_ref());
+            if keyword.is_empty() {
+                continue;
+            }
+            let node = tokenize(&keyword).fold(&mut self.trie, |node, (_, word)| {
+                node.children.entry(word.to_string()).or_default()
+            });
+            node.keyword = Some(keyword.clone());
+            self.keywords
+                .entry(slug.to_string())
+                .or_default()
+                .insert(keyword.clone());
             let slugs = self.map.entry(keyword).or_default();
             slugs.insert(slug.to_string());
         }
     }
 
+    /// the keywords of a slug, in sorted order
+    pub fn keywords_of(&self, slug: &str) -> impl Iterator<Item = &str> {
+        self.keywords
+            .get(normalize_slug(slug))
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+
+    /// Remembers the vocabulary of the markdown the slug was indexed from.
+    pub fn set_words(&mut self, slug: &str, markdown: &str) {
+        self.words.insert(
+            normalize_slug(slug).to_string(),
+            crate::relevance::vocabulary(markdown),
+        );
+    }
+
+    /// the vocabulary of the markdown the slug was indexed from
+    pub fn words(&self, slug: &str) -> Option<&HashSet<String>> {
+        self.words.get(normalize_slug(slug))
+    }
+
+    /// whether the slug has keywords
+    pub fn contains_slug(&self, slug: &str) -> bool {
+        self.keywords.contains_key(normalize_slug(slug))
+    }
+
+    /// the slugs for a keyword, in sorted order
     pub fn get(&self, keyword: &str) -> Option<impl Iterator<Item = &str>> {
         let set = self.map.get(keyword)?;
         Some(set.iter().map(String::as_ref))
     }
+
+    /// Remembers the markdown the slug was indexed from.
+    pub fn set_path(&mut self, slug: &str, path: &Path) {
+        let slug = normalize_slug(slug);
+        if let Some(previous) = self.paths.insert(slug.to_string(), path.to_owned()) {
+            if previous != path {
+                log::warn!(
+                    "slug {} is used by both {} and {}",
+                    slug,
+                    previous.to_string_lossy(),
+                    path.to_string_lossy()
+                );
+            }
+        }
+        self.slugs_by_path.insert(path_key(path), slug.to_string());
+    }
+
+    /// The slug indexed from the markdown at the path, which may leave out
+    /// the file extension.
+    pub fn slug_at(&self, path: &Path) -> Option<&str> {
+        self.slugs_by_path.get(&path_key(path)).map(String::as_str)
+    }
+
+    /// the markdown the slug was indexed from
+    pub fn path(&self, slug: &str) -> Option<&Path> {
+        self.paths.get(normalize_slug(slug)).map(PathBuf::as_path)
+    }
+
+    /// Finds every keyword occurrence in a sequence of positioned tokens,
+    /// including overlapping ones. Each token is lowercased once and the
+    /// trie is walked from every token, so no n-grams are ever built.
+    pub fn find<'a, 'b, I>(&'a self, words: I) -> Vec<Match<'a>>
+    where
+        I: IntoIterator<Item = (usize, &'b str)>,
+    {
+        let words: Vec<_> = words
+            .into_iter()
+            .map(|(start, word)| (start, start + word.len(), word.to_lowercase()))
+            .collect();
+
+        let mut matches = Vec::new();
+        for (i, (start, _, _)) in words.iter().enumerate() {
+            let mut node = &self.trie;
+            for (_, end, word) in &words[i..] {
+                node = match node.children.get(word) {
+                    Some(child) => child,
+                    None => break,
+                };
+                if let Some(keyword) = &node.keyword {
+                    matches.push(Match {
+                        start: *start,
+                        end: *end,
+                        keyword,
+                    });
+                }
+            }
+        }
+        matches
+    }
+}
+
+/// lowercases the keyword and collapses runs of whitespace into single spaces
+pub fn normalize(keyword: &str) -> Keyword {
+    itertools::join(keyword.split_whitespace().map(str::to_lowercase), " ")
+}
+
+/// Slugs are stored and looked up without surrounding whitespace.
+pub fn normalize_slug(slug: &str) -> &str {
+    slug.trim()
 }
 
 #[cfg(test)]
@@ -73,4 +223,84 @@ mod tests {
         let keywords = Keywords::new();
         assert!(keywords.get("non-existent-keyword").is_none());
     }
+
+    #[test]
+    fn test_keywords_insert_normalizes_whitespace() {
+        let mut keywords = Keywords::new();
+        keywords.insert("example-slug", &["  Front   Matter ", "   "]);
+
+        assert!(keywords.get("front matter").is_some());
+        assert_eq!(keywords.map.len(), 1);
+    }
+
+    #[test]
+    fn test_keywords_path() {
+        let mut keywords = Keywords::new();
+        keywords.insert("example-slug", &["example_keyword"]);
+        keywords.set_path(" example-slug ", Path::new("docs/example.md"));
+
+        assert_eq!(
+            keywords.path("example-slug"),
+            Some(Path::new("docs/example.md"))
+        );
+        assert_eq!(keywords.path("other-slug"), None);
+        assert_eq!(
+            keywords.slug_at(Path::new("./docs/example.html")),
+            Some("example-slug")
+        );
+        assert_eq!(keywords.slug_at(Path::new("example")), None);
+    }
+
+    #[test]
+    fn test_keywords_find() {
+        let mut keywords = Keywords::new();
+        keywords.insert("a", &["front matter", "front matter parser", "tokio"]);
+
+        let text = "the Front Matter parser uses tokio";
+        let words = crate::ngram::positioned(text, text.split_whitespace());
+        assert_eq!(
+            keywords.find(words),
+            vec![
+                Match {
+                    start: 4,
+                    end: 16,
+                    keyword: "front matter"
+                },
+                Match {
+                    start: 4,
+                    end: 23,
+                    keyword: "front matter parser"
+                },
+                Match {
+                    start: 29,
+                    end: 34,
+                    keyword: "tokio"
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keywords_find_punctuation() {
+        let mut keywords = Keywords::new();
+        keywords.insert("a", &["front matter", "C++"]);
+
+        let text = "(Front Matter's) parser, in c++.";
+        let found: Vec<_> = keywords
+            .find(tokenize(text))
+            .into_iter()
+            .map(|m| &text[m.start..m.end])
+            .collect();
+        assert_eq!(found, vec!["Front Matter", "c++"]);
+    }
+
+    #[test]
+    fn test_keywords_find_no_partial_match() {
+        let mut keywords = Keywords::new();
+        keywords.insert("a", &["front matter parser"]);
+
+        let text = "the front matter is parsed";
+        let words = crate::ngram::positioned(text, text.split_whitespace());
+        assert!(keywords.find(words).is_empty());
+    }
 }
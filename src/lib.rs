@@ -0,0 +1,22 @@
+//! Auto-links a glob of markdowns by the keywords in their front matter.
+//!
+//! The `mdlinker` binary is a thin layer over this library: it indexes the
+//! keywords of every markdown with [`index::index`] and writes the linked
+//! markdowns with the functions in [`mod@write`]. Which of the keyword occurrences
+//! become links is decided by a [`policy::LinkPolicy`], so other policies can
+//! be plugged in besides [`policy::DefaultPolicy`].
+
+mod bigram;
+pub mod budget;
+pub mod document;
+mod front_matter;
+pub mod index;
+pub mod keyword;
+mod markdown;
+mod ngram;
+pub mod policy;
+pub mod relevance;
+mod slug;
+mod tokenize;
+mod url;
+pub mod write;
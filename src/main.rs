@@ -1,13 +1,7 @@
-mod bigram;
-mod document;
-mod front_matter;
-mod index;
-mod keyword;
-mod markdown;
-mod ngram;
-mod write;
+mod config;
 
 use clap::Parser;
+use mdlinker::{budget, document, index, keyword, policy, write};
 use std::path::PathBuf;
 
 /// A program to auto-link a glob of markdowns.
@@ -20,27 +14,347 @@ use std::path::PathBuf;
 /// slug: <slug>
 /// ---
 ///
+/// Markdowns without a slug get one derived from their path, see --slug-from.
+/// Other field names can be used with --slug-field and --keyword-fields.
+/// The same front matter may also be written as TOML between `+++` lines,
+/// or as a JSON object.
+///
 /// The program will read in all keywords and slugs from all markdowns
 /// and then identify uses of those keywords throughout the
 /// glob of markdowns and create links accordingly.
 #[derive(Debug, Parser)]
 struct Args {
     /// the glob of markdowns affected
-    glob: String,
+    glob: Option<String>,
     /// the path to the folder preserving folder structure
-    output: PathBuf,
+    output: Option<PathBuf>,
+    /// the config file to use instead of ./mdlinker.toml, whose keys mirror
+    /// the long flags below, which take precedence over it
+    #[arg(long, env = "MDLINKER_CONFIG")]
+    config: Option<PathBuf>,
+    /// rewrite the matched markdowns instead of writing to an output folder
+    #[arg(long, short, conflicts_with = "output")]
+    in_place: bool,
+    /// when rewriting in place, keep each original with this suffix (e.g. ".bak")
+    #[arg(long)]
+    backup_suffix: Option<String>,
+    /// when rewriting in place, keep each original in this folder preserving folder structure
+    #[arg(long)]
+    backup_dir: Option<PathBuf>,
+    /// list the markdowns that are not fully linked and exit with an error
+    /// if there are any, without writing anything
+    #[arg(long, conflicts_with_all = ["output", "in_place"])]
+    check: bool,
+    /// print a unified diff of the changes to each markdown, without writing anything
+    #[arg(long, conflicts_with_all = ["output", "in_place", "check"])]
+    diff: bool,
+    /// print the links that would be inserted into each markdown, without writing anything
+    #[arg(long, conflicts_with_all = ["output", "in_place", "check", "diff"])]
+    dry_run: bool,
+    /// pick randomly among equally good links using this seed,
+    /// instead of the default deterministic ordering
+    #[arg(long, env = "MDLINKER_SEED")]
+    seed: Option<u64>,
+    /// how the url of a link is built, from the placeholders {base}, {slug},
+    /// {path} (the target markdown), {relpath} (the target markdown relative to
+    /// the linking one) and {relstem} (the same, without the file extension)
+    /// [default: {slug}]
+    #[arg(long, env = "MDLINKER_URL_TEMPLATE")]
+    url_template: Option<String>,
+    /// the url substituted for {base} in the url template
+    #[arg(long, env = "MDLINKER_BASE_URL")]
+    base_url: Option<String>,
+    /// the containers besides paragraphs that links may be inserted into
+    /// [default: list,block-quote,table]
+    #[arg(long, value_enum, value_delimiter = ',')]
+    link_in: Option<Vec<write::Container>>,
+    /// the comrak extensions markdowns are parsed and rendered with, where gfm
+    /// enables all GitHub Flavored Markdown extensions
+    #[arg(long, value_enum, value_delimiter = ',')]
+    extensions: Option<Vec<write::Extension>>,
+    /// re-render each markdown instead of only inserting links into its source
+    #[arg(long, overrides_with = "no_reformat")]
+    reformat: bool,
+    /// only insert links into the source, even if the config says to reformat
+    #[arg(long, overrides_with = "reformat")]
+    no_reformat: bool,
+    /// how the slug of a markdown without one in its front matter is derived
+    /// from its path, where path is relative to the glob
+    /// [default: stem]
+    #[arg(long, value_enum)]
+    slug_from: Option<document::SlugStrategy>,
+    /// the front matter field holding the slug [default: slug]
+    #[arg(long)]
+    slug_field: Option<String>,
+    /// the front matter fields whose values are merged into the keywords
+    /// [default: keywords,title,aliases]
+    #[arg(long, value_delimiter = ',')]
+    keyword_fields: Option<Vec<String>>,
+    /// mark inserted links so that later runs replace them instead of
+    /// linking around them [default: none]
+    #[arg(long, value_enum)]
+    marker: Option<write::Marker>,
+    /// remove the marked links instead of linking
+    #[arg(long, conflicts_with = "dry_run")]
+    unlink: bool,
+    /// the most links inserted into a paragraph, 0 for no limit [default: 4]
+    #[arg(long)]
+    max_links_per_paragraph: Option<usize>,
+    /// the most links inserted into a markdown, 0 for no limit [default: 0]
+    #[arg(long)]
+    max_links_per_document: Option<usize>,
+    /// the most links to the same page in a markdown, 0 for no limit [default: 1]
+    #[arg(long)]
+    max_links_per_target: Option<usize>,
+    /// the fewest words between two links in a paragraph [default: 0]
+    #[arg(long)]
+    min_word_gap: Option<usize>,
+    /// count the links to each page afresh in every section
+    #[arg(long, overrides_with = "no_relink_sections")]
+    relink_sections: bool,
+    /// count the links to each page across sections, even if the config says otherwise
+    #[arg(long, overrides_with = "relink_sections")]
+    no_relink_sections: bool,
+}
+
+impl Args {
+    /// Fills in everything not given on the command line from the config.
+    fn merge(mut self, config: config::Config) -> anyhow::Result<Self> {
+        let has_mode =
+            self.output.is_some() || self.in_place || self.check || self.diff || self.dry_run;
+        if !has_mode {
+            self.output = config.output;
+            self.in_place = config.in_place;
+        }
+        // the backups of the config go with its mode, unless in place is asked for
+        let has_backup = self.backup_suffix.is_some() || self.backup_dir.is_some();
+        if !has_backup && (!has_mode || self.in_place) {
+            self.backup_suffix = config.backup_suffix;
+            self.backup_dir = config.backup_dir;
+        }
+        self.glob = self.glob.or(config.glob);
+        self.seed = self.seed.or(config.seed);
+        self.url_template = self.url_template.or(config.url_template);
+        self.base_url = self.base_url.or(config.base_url);
+        self.link_in = self.link_in.or(config.link_in);
+        self.extensions = self.extensions.or(config.extensions);
+        self.reformat = !self.no_reformat && (self.reformat || config.reformat);
+        self.slug_from = self.slug_from.or(config.slug_from);
+        self.slug_field = self.slug_field.or(config.slug_field);
+        self.keyword_fields = self.keyword_fields.or(config.keyword_fields);
+        self.marker = self.marker.or(config.marker);
+        self.max_links_per_paragraph = self
+            .max_links_per_paragraph
+            .or(config.max_links_per_paragraph);
+        self.max_links_per_document = self
+            .max_links_per_document
+            .or(config.max_links_per_document);
+        self.max_links_per_target = self.max_links_per_target.or(config.max_links_per_target);
+        self.min_word_gap = self.min_word_gap.or(config.min_word_gap);
+        self.relink_sections =
+            !self.no_relink_sections && (self.relink_sections || config.relink_sections);
+
+        if self.glob.is_none() {
+            anyhow::bail!("no glob of markdowns was given");
+        }
+        if self.output.is_some() && self.in_place {
+            anyhow::bail!("output and in-place cannot be used together");
+        }
+        if self.backup_suffix.is_some() && self.backup_dir.is_some() {
+            anyhow::bail!("backup-suffix and backup-dir cannot be used together");
+        }
+        if (self.backup_suffix.is_some() || self.backup_dir.is_some()) && !self.in_place {
+            anyhow::bail!("backup-suffix and backup-dir can only be used with in-place");
+        }
+        if self.unlink && self.marker.unwrap_or_default() == write::Marker::None {
+            anyhow::bail!("unlink needs a marker to recognise inserted links by");
+        }
+        if !(self.output.is_some() || self.in_place || self.check || self.diff || self.dry_run) {
+            anyhow::bail!(
+                "no output folder was given, use --in-place, --check, --diff or --dry-run instead"
+            );
+        }
+        Ok(self)
+    }
+
+    fn glob(&self) -> &str {
+        self.glob.as_deref().unwrap_or_default()
+    }
+
+    fn options(&self) -> anyhow::Result<write::Options> {
+        Ok(write::Options {
+            seed: self.seed,
+            reformat: self.reformat,
+            urls: write::UrlTemplate::new(
+                self.url_template.as_deref().unwrap_or("{slug}"),
+                self.base_url.as_deref().unwrap_or_default(),
+            )?,
+            containers: match &self.link_in {
+                Some(containers) => containers.iter().copied().collect(),
+                None => Default::default(),
+            },
+            extensions: self.extensions.iter().flatten().copied().collect(),
+            schema: self.schema(),
+            marker: self.marker.unwrap_or_default(),
+            unlink: self.unlink,
+        })
+    }
+
+    fn budget(&self) -> budget::Budget {
+        let default = budget::Budget::default();
+        budget::Budget {
+            per_paragraph: self
+                .max_links_per_paragraph
+                .unwrap_or(default.per_paragraph),
+            per_document: self.max_links_per_document.unwrap_or(default.per_document),
+            per_target: self.max_links_per_target.unwrap_or(default.per_target),
+            min_word_gap: self.min_word_gap.unwrap_or(default.min_word_gap),
+            relink_sections: self.relink_sections,
+        }
+    }
+
+    fn schema(&self) -> document::Schema {
+        let mut fields = document::Fields::default();
+        if let Some(slug_field) = &self.slug_field {
+            fields.slug = slug_field.clone();
+        }
+        if let Some(keyword_fields) = &self.keyword_fields {
+            fields.keywords = keyword_fields.clone();
+        }
+        document::Schema {
+            fields,
+            slugs: document::Slugger::new(self.slug_from.unwrap_or_default(), self.glob()),
+        }
+    }
+
+    fn destination(&self) -> write::Destination {
+        match &self.output {
+            Some(output) => write::Destination::Directory(output.clone()),
+            None => write::Destination::InPlace(match (&self.backup_suffix, &self.backup_dir) {
+                (Some(suffix), _) => Some(write::Backup::Suffix(suffix.clone())),
+                (_, Some(dir)) => Some(write::Backup::Directory(dir.clone())),
+                (None, None) => None,
+            }),
+        }
+    }
 }
 
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
     env_logger::init();
+    let config = config::Config::load(args.config.as_deref())?;
+    let args = args.merge(config)?;
+    let options = args.options()?;
     let mut keywords = keyword::Keywords::new();
+    let mut policy = policy::DefaultPolicy::new(args.budget(), args.seed);
 
     log::info!("indexing...");
-    index::index(&mut keywords, &args.glob)?;
+    index::index(&mut keywords, args.glob(), &options.schema)?;
+    if args.check {
+        log::info!("checking...");
+        let changed = write::check_glob(&keywords, args.glob(), &options, &mut policy)?;
+        for path in &changed {
+            println!("{}", path.to_string_lossy());
+        }
+        if !changed.is_empty() {
+            log::error!("{} markdowns are not fully linked", changed.len());
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if args.diff {
+        log::info!("diffing...");
+        write::diff_glob(
+            &keywords,
+            args.glob(),
+            &options,
+            &mut policy,
+            &mut std::io::stdout(),
+        )?;
+        return Ok(());
+    }
+
+    if args.dry_run {
+        log::info!("dry running...");
+        write::dry_run_glob(
+            &keywords,
+            args.glob(),
+            &options,
+            &mut policy,
+            &mut std::io::stdout(),
+        )?;
+        return Ok(());
+    }
+
     log::info!("updating...");
-    write::write_glob(&keywords, &args.glob, &args.output)?;
+    write::write_glob(
+        &keywords,
+        args.glob(),
+        &args.destination(),
+        &options,
+        &mut policy,
+    )?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merge(args: &[&str], config: config::Config) -> anyhow::Result<Args> {
+        let args = Args::try_parse_from(["mdlinker"].iter().chain(args))?;
+        args.merge(config)
+    }
+
+    #[test]
+    fn test_merge_backups() {
+        let in_place = config::Config {
+            glob: Some("*.md".to_string()),
+            in_place: true,
+            ..Default::default()
+        };
+        let args = merge(&["--backup-suffix", ".bak"], in_place.clone()).unwrap();
+        assert!(args.in_place);
+        assert_eq!(args.backup_suffix.as_deref(), Some(".bak"));
+
+        let output = config::Config {
+            glob: Some("*.md".to_string()),
+            output: Some(PathBuf::from("out")),
+            backup_suffix: Some(".bak".to_string()),
+            ..Default::default()
+        };
+        assert!(merge(&[], output).is_err());
+
+        // a backup of the config does not apply to another mode on the command line
+        let backed_up = config::Config {
+            backup_dir: Some(PathBuf::from("backups")),
+            ..in_place
+        };
+        let args = merge(&["--check"], backed_up.clone()).unwrap();
+        assert_eq!(args.backup_dir, None);
+        assert!(merge(&["--check", "--backup-dir", "b"], backed_up).is_err());
+    }
+
+    #[test]
+    fn test_merge_flags_override_config() {
+        let config = config::Config {
+            glob: Some("*.md".to_string()),
+            in_place: true,
+            reformat: true,
+            relink_sections: true,
+            ..Default::default()
+        };
+        let args = merge(&[], config.clone()).unwrap();
+        assert!(args.reformat && args.relink_sections);
+
+        let args = merge(&["--no-reformat", "--no-relink-sections"], config.clone()).unwrap();
+        assert!(!args.reformat && !args.relink_sections);
+
+        // the last of a flag and its negation wins
+        let args = merge(&["--no-reformat", "--reformat"], config).unwrap();
+        assert!(args.reformat);
+    }
+}
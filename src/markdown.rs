@@ -1,39 +1,192 @@
 use std::cell::RefCell;
+use std::collections::HashSet;
 
 use comrak::{
-    nodes::{Ast, AstNode, LineColumn, NodeLink, NodeValue},
-    Arena,
+    nodes::{Ast, AstNode, LineColumn, NodeLink, NodeValue, Sourcepos},
+    Arena, ComrakOptions,
 };
 
+/// A comrak extension that markdowns are parsed and rendered with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Extension {
+    /// GitHub Flavored Markdown: strikethrough, tagfilter, table, autolink,
+    /// tasklist and footnotes
+    Gfm,
+    Strikethrough,
+    Tagfilter,
+    Table,
+    Autolink,
+    Tasklist,
+    Footnotes,
+    Superscript,
+    DescriptionLists,
+}
+
+/// The set of comrak extensions that markdowns are parsed and rendered with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Extensions(HashSet<Extension>);
+
+impl FromIterator<Extension> for Extensions {
+    fn from_iter<I: IntoIterator<Item = Extension>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl Extensions {
+    fn enabled(&self, extension: Extension) -> bool {
+        let in_gfm = !matches!(
+            extension,
+            Extension::Superscript | Extension::DescriptionLists
+        );
+        self.0.contains(&extension) || (in_gfm && self.0.contains(&Extension::Gfm))
+    }
+
+    /// the comrak options to both parse and render with
+    pub fn comrak_options(&self) -> ComrakOptions {
+        let mut options = ComrakOptions::default();
+        let extension = &mut options.extension;
+        extension.strikethrough = self.enabled(Extension::Strikethrough);
+        extension.tagfilter = self.enabled(Extension::Tagfilter);
+        extension.table = self.enabled(Extension::Table);
+        extension.autolink = self.enabled(Extension::Autolink);
+        extension.tasklist = self.enabled(Extension::Tasklist);
+        extension.footnotes = self.enabled(Extension::Footnotes);
+        extension.superscript = self.enabled(Extension::Superscript);
+        extension.description_lists = self.enabled(Extension::DescriptionLists);
+        options
+    }
+}
+
+/// A kind of markdown container that links may be inserted into.
+/// Paragraphs are always linked, while code, html, links and images never are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Container {
+    /// bullet and ordered lists, including paragraphs nested in their items
+    List,
+    BlockQuote,
+    Table,
+    DescriptionList,
+    Heading,
+    Strong,
+    Emph,
+}
+
+/// The set of containers that links may be inserted into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Containers(HashSet<Container>);
+
+impl Default for Containers {
+    fn default() -> Self {
+        [Container::List, Container::BlockQuote, Container::Table]
+            .into_iter()
+            .collect()
+    }
+}
+
+impl FromIterator<Container> for Containers {
+    fn from_iter<I: IntoIterator<Item = Container>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl Containers {
+    /// whether links may be inserted into the text within a node
+    fn allows(&self, value: &NodeValue) -> bool {
+        let container = match value {
+            NodeValue::Document
+            | NodeValue::Paragraph
+            | NodeValue::TableRow(..)
+            | NodeValue::TableCell => return true,
+            NodeValue::List(..) | NodeValue::Item(..) | NodeValue::TaskItem(..) => Container::List,
+            NodeValue::BlockQuote => Container::BlockQuote,
+            NodeValue::Table(..) => Container::Table,
+            NodeValue::DescriptionList
+            | NodeValue::DescriptionItem(..)
+            | NodeValue::DescriptionTerm
+            | NodeValue::DescriptionDetails => Container::DescriptionList,
+            NodeValue::Heading(..) => Container::Heading,
+            NodeValue::Strong => Container::Strong,
+            NodeValue::Emph => Container::Emph,
+            _ => return false,
+        };
+        self.0.contains(&container)
+    }
+}
+
+const REGION_OFF: &str = "<!-- mdlinker:off -->";
+const REGION_ON: &str = "<!-- mdlinker:on -->";
+
+/// Whether the html ends a region that is not linked (`Some(true)`), starts
+/// one (`Some(false)`), or does neither.
+fn region_marker(html: &str) -> Option<bool> {
+    match (html.rfind(REGION_OFF), html.rfind(REGION_ON)) {
+        (Some(off), Some(on)) => Some(off > on),
+        (Some(_), None) => Some(true),
+        (None, Some(_)) => Some(false),
+        (None, None) => None,
+    }
+}
+
+/// Follows the region markers within a node that is not descended into.
+fn skip_regions<'a>(node: &'a AstNode<'a>, off: &mut bool) {
+    for node in node.descendants() {
+        let marker = match &node.data.borrow().value {
+            NodeValue::HtmlBlock(block) => region_marker(&block.literal),
+            NodeValue::HtmlInline(html) => region_marker(html),
+            _ => None,
+        };
+        if let Some(marker) = marker {
+            *off = marker;
+        }
+    }
+}
+
 // Traverse the AST, applying the provided helper function to text nodes
-// The helper function can return an altered version of the text
-// Certain node types are ignored and not descended into, to avoid altering text within them
-fn alter_text<'a, F>(arena: &'a Arena<AstNode<'a>>, node: &'a AstNode<'a>, helper: &mut F)
-where
-    F: FnMut(&'a Arena<AstNode<'a>>, &str) -> Option<Vec<&'a AstNode<'a>>>,
+// The helper function is given the text and its position in the source, and
+// can return an altered version of the text
+// Only the containers allowed are descended into, to avoid altering text within
+// the others, and text between `<!-- mdlinker:off -->` and
+// `<!-- mdlinker:on -->` is left alone
+fn alter_text<'a, F>(
+    arena: &'a Arena<AstNode<'a>>,
+    node: &'a AstNode<'a>,
+    containers: &Containers,
+    helper: &mut F,
+) where
+    F: FnMut(&'a Arena<AstNode<'a>>, &str, Sourcepos) -> Option<Vec<&'a AstNode<'a>>>,
+{
+    alter_text_regions(arena, node, containers, &mut false, helper)
+}
+
+fn alter_text_regions<'a, F>(
+    arena: &'a Arena<AstNode<'a>>,
+    node: &'a AstNode<'a>,
+    containers: &Containers,
+    off: &mut bool,
+    helper: &mut F,
+) where
+    F: FnMut(&'a Arena<AstNode<'a>>, &str, Sourcepos) -> Option<Vec<&'a AstNode<'a>>>,
 {
     match node.data.borrow().value {
-        // Ignored node types
-        NodeValue::BlockQuote
-        | NodeValue::List(..)
-        | NodeValue::Item(..)
-        | NodeValue::DescriptionItem(..)
-        | NodeValue::DescriptionTerm
-        | NodeValue::DescriptionDetails
-        | NodeValue::DescriptionList
-        | NodeValue::CodeBlock(..)
+        // Ignored node types, whatever the containers allowed
+        NodeValue::CodeBlock(..)
         | NodeValue::HtmlBlock(..)
-        | NodeValue::Table(..)
         | NodeValue::ThematicBreak
-        | NodeValue::Strong
-        | NodeValue::Emph
         | NodeValue::Link(..)
-        | NodeValue::Image(..) => (),
-        // For paragraph nodes, apply the helper function to the text nodes within it
-        NodeValue::Paragraph => {
+        | NodeValue::Image(..) => skip_regions(node, off),
+        ref value if !containers.allows(value) => skip_regions(node, off),
+        // Apply the helper function to the text nodes within it, and recurse into
+        // all other children
+        _ => {
             for c in node.children() {
-                if let NodeValue::Text(literal) = &c.data.borrow().value {
-                    if let Some(v) = helper(arena, literal) {
+                let data = c.data.borrow();
+                if let NodeValue::Text(literal) = &data.value {
+                    if *off {
+                        continue;
+                    }
+                    if let Some(v) = helper(arena, literal, data.sourcepos) {
                         // if helper returns something, replace the current node with
                         // what was returned.
                         for item in v.into_iter() {
@@ -41,72 +194,329 @@ where
                         }
                         c.detach()
                     }
+                } else {
+                    alter_text_regions(arena, c, containers, off, helper);
                 }
             }
         }
-        // For all other node types, recurse into their children
-        _ => {
-            for c in node.children() {
-                alter_text(arena, c, helper);
+    }
+}
+
+/// How the links mdlinker inserts are marked, so that later runs can tell
+/// them apart from links written by hand.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Marker {
+    /// links are not marked
+    #[default]
+    None,
+    /// links get the title "mdlinker"
+    Title,
+    /// links are followed by `<!-- mdlinker -->`
+    Comment,
+}
+
+const MARKER_TITLE: &str = "mdlinker";
+const MARKER_COMMENT: &str = "<!-- mdlinker -->";
+
+impl Marker {
+    fn title(&self) -> &'static str {
+        match self {
+            Marker::Title => MARKER_TITLE,
+            _ => "",
+        }
+    }
+
+    /// The source of a link after its text, as it is written by
+    /// `splice_links`, e.g. `](url "mdlinker")`.
+    fn link_end(&self, url: &str) -> String {
+        let url = if url.contains(|c: char| c.is_whitespace() || c == '(' || c == ')') {
+            format!("<{url}>")
+        } else {
+            url.to_string()
+        };
+        match self {
+            Marker::None => format!("]({url})"),
+            Marker::Title => format!("]({url} \"{MARKER_TITLE}\")"),
+            Marker::Comment => format!("]({url}){MARKER_COMMENT}"),
+        }
+    }
+
+    /// whether the link node was inserted by mdlinker
+    fn is_marked<'a>(&self, link: &'a AstNode<'a>) -> bool {
+        match (self, &link.data.borrow().value) {
+            (Marker::Title, NodeValue::Link(link)) => link.title == MARKER_TITLE,
+            (Marker::Comment, NodeValue::Link(_)) => link.next_sibling().is_some_and(|next| {
+                matches!(&next.data.borrow().value, NodeValue::HtmlInline(html) if html == MARKER_COMMENT)
+            }),
+            _ => false,
+        }
+    }
+}
+
+/// Removes the links marked by mdlinker from the source, keeping their text.
+/// `root` must be parsed from the source. Links are found by their position
+/// in the source, and marked links whose source does not look like a link
+/// there are left alone.
+pub fn strip_links<'a>(source: &str, root: &'a AstNode<'a>, marker: Marker) -> String {
+    let source_map = SourceMap::new(source);
+    let mut out = String::with_capacity(source.len());
+    let mut cur = 0;
+    for node in root.descendants().filter(|node| marker.is_marked(node)) {
+        let Some(block) = node
+            .ancestors()
+            .find(|node| node.data.borrow().value.contains_inlines())
+        else {
+            continue;
+        };
+        let block = block.data.borrow().sourcepos.start;
+        let data = node.data.borrow();
+        let span = source_map
+            .span(block, data.sourcepos)
+            .filter(|(start, _)| *start >= cur)
+            .and_then(|(start, end)| Some((start, label_end(&source[start..end])?, end)));
+        let Some((start, label_end, mut end)) = span else {
+            log::warn!(
+                "marked link at line {} not found in the source, so it is kept",
+                data.sourcepos.start.line
+            );
+            continue;
+        };
+        if marker == Marker::Comment && source[end..].starts_with(MARKER_COMMENT) {
+            end += MARKER_COMMENT.len();
+        }
+        out.push_str(&source[cur..start]);
+        out.push_str(&source[start + 1..start + label_end]);
+        cur = end;
+    }
+    out.push_str(&source[cur..]);
+    out
+}
+
+/// The byte offset of the `]` closing the text of the link, if the source
+/// is an inline link `[text](...)`.
+fn label_end(link: &str) -> Option<usize> {
+    if !link.starts_with('[') || !link.ends_with(')') {
+        return None;
+    }
+    let mut depth = 0;
+    let mut escaped = false;
+    for (i, c) in link.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return link[i..].starts_with("](").then_some(i);
+                }
             }
+            _ => {}
         }
     }
+    None
 }
 
-pub fn add_links<'a, F>(arena: &'a Arena<AstNode<'a>>, node: &'a AstNode<'a>, mut helper: F)
-where
-    F: FnMut(&str) -> Vec<(usize, usize, String)>,
+pub fn add_links<'a, F>(
+    arena: &'a Arena<AstNode<'a>>,
+    node: &'a AstNode<'a>,
+    containers: &Containers,
+    marker: Marker,
+    mut helper: F,
+) where
+    F: FnMut(&str, Sourcepos) -> Vec<(usize, usize, String)>,
 {
-    alter_text(arena, node, &mut |arena, text: &str| {
-        let mut new_nodes: Vec<&AstNode> = vec![];
+    alter_text(
+        arena,
+        node,
+        containers,
+        &mut |arena, text: &str, sourcepos| {
+            let mut new_nodes: Vec<&AstNode> = vec![];
+
+            let mut links = helper(text, sourcepos);
+            links.sort();
+
+            if links.is_empty() {
+                return None;
+            }
+
+            let segment = |start, end| {
+                let segment = &text[start..end];
+                let segment_node = AstNode::new(RefCell::new(Ast::new(
+                    NodeValue::Text(segment.to_string()),
+                    LineColumn { line: 1, column: 1 },
+                )));
+                arena.alloc(segment_node)
+            };
+
+            let mut cur = 0;
+            for (start, length, url) in links {
+                if cur < start {
+                    new_nodes.push(segment(cur, start));
+                }
+
+                let link = NodeLink {
+                    url,
+                    title: marker.title().to_string(),
+                };
+                let link_node = arena.alloc(AstNode::new(RefCell::new(Ast::new(
+                    NodeValue::Link(link),
+                    LineColumn { line: 1, column: 1 },
+                ))));
+                // Add a Google text node as a child of the link node
+                let text_node = segment(start, start + length);
+                link_node.prepend(text_node);
+                new_nodes.push(link_node);
+                if marker == Marker::Comment {
+                    new_nodes.push(arena.alloc(AstNode::new(RefCell::new(Ast::new(
+                        NodeValue::HtmlInline(MARKER_COMMENT.to_string()),
+                        LineColumn { line: 1, column: 1 },
+                    )))));
+                }
+                cur = start + length;
+            }
+
+            if cur < text.len() {
+                new_nodes.push(segment(cur, text.len()));
+            }
+
+            if new_nodes.is_empty() {
+                None
+            } else {
+                Some(new_nodes)
+            }
+        },
+    );
+}
 
-        let mut links = helper(text);
-        links.sort();
+/// The lines all headings start on, in document order.
+pub fn heading_lines<'a>(node: &'a AstNode<'a>) -> Vec<usize> {
+    node.descendants()
+        .filter_map(|node| {
+            let data = node.data.borrow();
+            match data.value {
+                NodeValue::Heading(_) => Some(data.sourcepos.start.line),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// The urls of all links already in the markdown, in document order.
+pub fn link_urls<'a>(node: &'a AstNode<'a>) -> Vec<String> {
+    node.descendants()
+        .filter_map(|node| match &node.data.borrow().value {
+            NodeValue::Link(link) => Some(link.url.clone()),
+            _ => None,
+        })
+        .collect()
+}
 
-        if links.is_empty() {
-            return None;
+/// Maps comrak's line/column source positions back onto byte offsets of the
+/// markdown they were parsed from.
+pub struct SourceMap<'s> {
+    source: &'s str,
+    line_starts: Vec<usize>,
+}
+
+impl<'s> SourceMap<'s> {
+    pub fn new(source: &'s str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            source,
+            line_starts,
         }
+    }
 
-        let segment = |start, end| {
-            let segment = &text[start..end];
-            let segment_node = AstNode::new(RefCell::new(Ast::new(
-                NodeValue::Text(segment.to_string()),
-                LineColumn { line: 1, column: 1 },
-            )));
-            arena.alloc(segment_node)
+    // the byte range of a line, counting from 1
+    fn line(&self, line: usize) -> Option<(usize, usize)> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = match self.line_starts.get(line) {
+            Some(next) => next - 1,
+            None => self.source.len(),
         };
+        Some((start, end))
+    }
 
-        let mut cur = 0;
-        for (start, length, url) in links {
-            if cur < start {
-                new_nodes.push(segment(cur, start));
-            }
+    /// The byte offset of a position comrak reports for an inline node of the
+    /// block starting at `block`. Columns are exact on the first line of the
+    /// block, but on later lines comrak counts them from the start of the
+    /// block as if the container markers and indentation were not there.
+    fn offset(&self, block: LineColumn, at: LineColumn) -> Option<usize> {
+        let (start, end) = self.line(at.line)?;
+        let offset = if at.line == block.line {
+            start + at.column.checked_sub(1)?
+        } else {
+            let line = &self.source[start..end];
+            let indent = line.len() - line.trim_start_matches([' ', '\t', '>']).len();
+            start + indent + at.column.checked_sub(block.column)?
+        };
+        (offset < end).then_some(offset)
+    }
 
-            let link = NodeLink {
-                url,
-                title: "".to_string(),
-            };
-            let link_node = arena.alloc(AstNode::new(RefCell::new(Ast::new(
-                NodeValue::Link(link),
-                LineColumn { line: 1, column: 1 },
-            ))));
-            // Add a Google text node as a child of the link node
-            let text_node = segment(start, start + length);
-            link_node.prepend(text_node);
-            new_nodes.push(link_node);
-            cur = start + length;
+    /// The byte range of an inline node at `sourcepos` in the block starting
+    /// at `block`.
+    pub fn span(&self, block: LineColumn, sourcepos: Sourcepos) -> Option<(usize, usize)> {
+        let start = self.offset(block, sourcepos.start)?;
+        let end = self.offset(block, sourcepos.end)? + 1;
+        let valid =
+            start < end && self.source.is_char_boundary(start) && self.source.is_char_boundary(end);
+        valid.then_some((start, end))
+    }
+
+    /// Finds the byte offset of `text` in the source, where `text` is the
+    /// beginning of a text node at `sourcepos`. comrak does not report
+    /// reliable columns on continuation lines, so if the text is not at the
+    /// reported column it has to occur exactly once on the reported line.
+    /// Returns `None` when the text cannot be found verbatim, e.g. because
+    /// it was unescaped while parsing.
+    pub fn locate(&self, sourcepos: Sourcepos, text: &str) -> Option<usize> {
+        let (start, end) = self.line(sourcepos.start.line)?;
+        let guess = start + sourcepos.start.column.checked_sub(1)?;
+        if guess <= end
+            && self
+                .source
+                .get(guess..)
+                .is_some_and(|s| s.starts_with(text))
+        {
+            return Some(guess);
         }
 
-        if cur < text.len() {
-            new_nodes.push(segment(cur, text.len()));
+        let line = self.source.get(start..end)?;
+        let mut found = line.match_indices(text).map(|(i, _)| start + i);
+        match (found.next(), found.next()) {
+            (Some(offset), None) => Some(offset),
+            _ => None,
         }
+    }
+}
 
-        if new_nodes.is_empty() {
-            None
-        } else {
-            Some(new_nodes)
+/// Inserts links around the given `(start, length, url)` byte ranges of the
+/// source and leaves every other byte untouched.
+pub fn splice_links(
+    source: &str,
+    mut links: Vec<(usize, usize, String)>,
+    marker: Marker,
+) -> String {
+    links.sort();
+
+    let mut out = String::with_capacity(source.len() + links.len() * 16);
+    let mut cur = 0;
+    for (start, length, url) in links {
+        if start < cur {
+            continue; // overlaps the previous link
         }
-    });
+        out.push_str(&source[cur..start]);
+        out.push('[');
+        out.push_str(&source[start..start + length]);
+        out.push_str(&marker.link_end(&url));
+        cur = start + length;
+    }
+    out.push_str(&source[cur..]);
+    out
 }
 
 #[cfg(test)]
@@ -133,14 +543,19 @@ Another paragraph.";
         let arena = Arena::new();
         let root = parse_document(&arena, markdown, &ComrakOptions::default());
 
-        alter_text(&arena, root, &mut |arena, text: &str| {
-            let capitalized_text = text.to_uppercase();
-            let new_node = AstNode::new(RefCell::new(Ast::new(
-                NodeValue::Text(capitalized_text),
-                LineColumn { line: 1, column: 1 },
-            )));
-            Some(vec![arena.alloc(new_node)])
-        });
+        alter_text(
+            &arena,
+            root,
+            &Containers::default(),
+            &mut |arena, text: &str, _| {
+                let capitalized_text = text.to_uppercase();
+                let new_node = AstNode::new(RefCell::new(Ast::new(
+                    NodeValue::Text(capitalized_text),
+                    LineColumn { line: 1, column: 1 },
+                )));
+                Some(vec![arena.alloc(new_node)])
+            },
+        );
 
         let mut buf = vec![];
         comrak::format_commonmark(root, &ComrakOptions::default(), &mut buf).unwrap();
@@ -164,49 +579,393 @@ Another paragraph.";
         let arena = Arena::new();
         let root = parse_document(&arena, markdown, &ComrakOptions::default());
 
-        alter_text(&arena, root, &mut |arena, text: &str| {
-            let mut new_nodes: Vec<&AstNode> = vec![];
+        alter_text(
+            &arena,
+            root,
+            &Containers::default(),
+            &mut |arena, text: &str, _| {
+                let mut new_nodes: Vec<&AstNode> = vec![];
 
-            for (i, segment) in text.split("Google").enumerate() {
-                // If this is not the first segment, prepend a Google link
-                if i != 0 {
-                    let link = NodeLink {
-                        url: "https://www.google.com".to_string(),
-                        title: "".to_string(),
-                    };
-                    let link_node = arena.alloc(AstNode::new(RefCell::new(Ast::new(
-                        NodeValue::Link(link),
-                        LineColumn { line: 1, column: 1 },
-                    ))));
-                    // Add a Google text node as a child of the link node
-                    let google_text_node = arena.alloc(AstNode::new(RefCell::new(Ast::new(
-                        NodeValue::Text("Google".to_string()),
+                for (i, segment) in text.split("Google").enumerate() {
+                    // If this is not the first segment, prepend a Google link
+                    if i != 0 {
+                        let link = NodeLink {
+                            url: "https://www.google.com".to_string(),
+                            title: "".to_string(),
+                        };
+                        let link_node = arena.alloc(AstNode::new(RefCell::new(Ast::new(
+                            NodeValue::Link(link),
+                            LineColumn { line: 1, column: 1 },
+                        ))));
+                        // Add a Google text node as a child of the link node
+                        let google_text_node = arena.alloc(AstNode::new(RefCell::new(Ast::new(
+                            NodeValue::Text("Google".to_string()),
+                            LineColumn { line: 1, column: 1 },
+                        ))));
+                        link_node.prepend(google_text_node);
+                        new_nodes.push(link_node);
+                    }
+
+                    // Add a new text node for this segment
+                    let segment_node = AstNode::new(RefCell::new(Ast::new(
+                        NodeValue::Text(segment.to_string()),
                         LineColumn { line: 1, column: 1 },
-                    ))));
-                    link_node.prepend(google_text_node);
-                    new_nodes.push(link_node);
+                    )));
+                    new_nodes.push(arena.alloc(segment_node));
                 }
 
-                // Add a new text node for this segment
-                let segment_node = AstNode::new(RefCell::new(Ast::new(
-                    NodeValue::Text(segment.to_string()),
-                    LineColumn { line: 1, column: 1 },
-                )));
-                new_nodes.push(arena.alloc(segment_node));
+                if new_nodes.is_empty() {
+                    None
+                } else {
+                    Some(new_nodes)
+                }
+            },
+        );
+
+        let mut buf = vec![];
+        comrak::format_commonmark(root, &ComrakOptions::default(), &mut buf).unwrap();
+        let result = String::from_utf8(buf).unwrap();
+
+        assert!(result.contains("This is a paragraph with [Google](https://www.google.com)."));
+        assert!(result.contains("Another paragraph."));
+    }
+
+    fn text_nodes<'a>(node: &'a AstNode<'a>, out: &mut Vec<(String, Sourcepos)>) {
+        let data = node.data.borrow();
+        if let NodeValue::Text(literal) = &data.value {
+            out.push((literal.clone(), data.sourcepos));
+        }
+        for c in node.children() {
+            text_nodes(c, out);
+        }
+    }
+
+    // Test that text nodes are found in the source, even where comrak's columns are off
+    #[test]
+    fn test_source_map_locate() {
+        let markdown = "# Heading *x* here
+
+A para with **bold** text
+  second line \\* escaped
+
+- item one
+  continued
+
+  indented para
+   with spaces
+ünïcode text
+";
+
+        let arena = Arena::new();
+        let root = parse_document(&arena, markdown, &ComrakOptions::default());
+        let mut nodes = vec![];
+        text_nodes(root, &mut nodes);
+
+        let map = SourceMap::new(markdown);
+        for (literal, sourcepos) in nodes {
+            match map.locate(sourcepos, &literal) {
+                Some(offset) => assert_eq!(&markdown[offset..offset + literal.len()], literal),
+                None => assert_eq!(literal, "second line * escaped"),
             }
+        }
+    }
 
-            if new_nodes.is_empty() {
-                None
+    #[test]
+    fn test_source_map_locate_multibyte_continuation() {
+        // comrak's columns on these lines point into the middle of the ü
+        for markdown in ["   ab\n xü tokio", "> ab\nxü tokio"] {
+            let arena = Arena::new();
+            let root = parse_document(&arena, markdown, &ComrakOptions::default());
+            let mut nodes = vec![];
+            text_nodes(root, &mut nodes);
+
+            let map = SourceMap::new(markdown);
+            let (literal, sourcepos) = nodes.last().unwrap();
+            assert_eq!(literal, "xü tokio");
+            let offset = map.locate(*sourcepos, literal).unwrap();
+            assert_eq!(&markdown[offset..], "xü tokio");
+        }
+    }
+
+    #[test]
+    fn test_splice_links() {
+        let source = "A *para* with  odd   spacing\nand a second line.";
+        let result = splice_links(
+            source,
+            vec![
+                (35, 6, "second-line".to_string()),
+                (2, 6, "para page".to_string()),
+            ],
+            Marker::None,
+        );
+        assert_eq!(
+            result,
+            "A [*para*](<para page>) with  odd   spacing\nand a [second](second-line) line."
+        );
+    }
+
+    #[test]
+    fn test_strip_marked_links() {
+        let source = "Some text\nwith a line.";
+        let links = vec![(0, 4, "some".to_string()), (17, 4, "a line".to_string())];
+        let options = ComrakOptions::default();
+
+        for (marker, linked) in [
+            (
+                Marker::Title,
+                "[Some](some \"mdlinker\") text\nwith a [line](<a line> \"mdlinker\").",
+            ),
+            (
+                Marker::Comment,
+                "[Some](some)<!-- mdlinker --> text\nwith a [line](<a line>)<!-- mdlinker -->.",
+            ),
+        ] {
+            let spliced = splice_links(source, links.clone(), marker);
+            assert_eq!(spliced, linked);
+
+            let arena = Arena::new();
+            let root = parse_document(&arena, &spliced, &options);
+            assert_eq!(strip_links(&spliced, root, marker), source);
+            // links written by hand are kept
+            let root = parse_document(&arena, &spliced, &options);
+            let other = if marker == Marker::Title {
+                Marker::Comment
             } else {
-                Some(new_nodes)
-            }
+                Marker::Title
+            };
+            assert_eq!(strip_links(&spliced, root, other), spliced);
+        }
+
+        let source = "[Some](some) text and [more](more \"mdlinker\")";
+        let arena = Arena::new();
+        let root = parse_document(&arena, source, &options);
+        assert_eq!(
+            strip_links(source, root, Marker::Title),
+            "[Some](some) text and more"
+        );
+
+        for (source, stripped) in [
+            // urls that comrak decodes
+            (
+                "[Q&amp;A](q&amp;a \"mdlinker\") and [x](a\\)b \"mdlinker\")",
+                "Q&amp;A and x",
+            ),
+            // the marked link written out in a code span before it
+            (
+                "`[tokio](tokio \"mdlinker\")` and [tokio](tokio \"mdlinker\")",
+                "`[tokio](tokio \"mdlinker\")` and tokio",
+            ),
+            // continuation lines in and out of block quotes
+            (
+                "first\n   then [a](a \"mdlinker\")\n\n> quoted\nlazy [b](b \"mdlinker\")\n>   more [c](c \"mdlinker\")",
+                "first\n   then a\n\n> quoted\nlazy b\n>   more c",
+            ),
+            (
+                "- item [a](a \"mdlinker\")\n  next [b](b \"mdlinker\")",
+                "- item a\n  next b",
+            ),
+        ] {
+            let root = parse_document(&arena, source, &options);
+            assert_eq!(strip_links(source, root, Marker::Title), stripped);
+        }
+    }
+
+    #[test]
+    fn test_add_marked_links() {
+        let options = ComrakOptions::default();
+        for (marker, expected) in [
+            (Marker::Title, "A [word](url \"mdlinker\")\n"),
+            (Marker::Comment, "A [word](url)<!-- mdlinker -->\n"),
+        ] {
+            let arena = Arena::new();
+            let root = parse_document(&arena, "A word", &options);
+            add_links(&arena, root, &Containers::default(), marker, |_, _| {
+                vec![(2, 4, "url".to_string())]
+            });
+            let mut out = vec![];
+            comrak::format_commonmark(root, &options, &mut out).unwrap();
+            assert_eq!(String::from_utf8(out).unwrap(), expected);
+        }
+    }
+
+    fn capitalize<'a>(arena: &'a Arena<AstNode<'a>>, text: &str) -> Option<Vec<&'a AstNode<'a>>> {
+        let new_node = AstNode::new(RefCell::new(Ast::new(
+            NodeValue::Text(text.to_uppercase()),
+            LineColumn { line: 1, column: 1 },
+        )));
+        Some(vec![arena.alloc(new_node)])
+    }
+
+    const CONTAINERS_MARKDOWN: &str = "
+# heading
+
+paragraph with *emph* and [link](url) and `code`
+
+- list item
+- > quoted item
+
+  nested paragraph
+
+> quote
+
+| table |
+| ----- |
+| cell  |
+
+```
+code block
+```
+";
+
+    fn capitalize_in(containers: &Containers) -> String {
+        let arena = Arena::new();
+        let mut options = ComrakOptions::default();
+        options.extension.table = true;
+        let root = parse_document(&arena, CONTAINERS_MARKDOWN, &options);
+
+        alter_text(&arena, root, containers, &mut |arena, text: &str, _| {
+            capitalize(arena, text)
         });
 
         let mut buf = vec![];
-        comrak::format_commonmark(root, &ComrakOptions::default(), &mut buf).unwrap();
+        comrak::format_commonmark(root, &options, &mut buf).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    // Test that the default containers are descended into, but nothing else
+    #[test]
+    fn test_default_containers() {
+        let result = capitalize_in(&Containers::default());
+
+        assert!(result.contains("# heading"));
+        assert!(result.contains("PARAGRAPH WITH *emph* AND [link](url) AND `code`"));
+        assert!(result.contains("- LIST ITEM"));
+        assert!(result.contains("- > QUOTED ITEM"));
+        assert!(result.contains("  NESTED PARAGRAPH"));
+        assert!(result.contains("> QUOTE"));
+        assert!(result.contains("| TABLE |"));
+        assert!(result.contains("| CELL |"));
+        assert!(result.contains("code block"));
+    }
+
+    // Test that only paragraphs are descended into without any containers
+    #[test]
+    fn test_no_containers() {
+        let result = capitalize_in(&Containers::from_iter([]));
+
+        assert!(result.contains("PARAGRAPH WITH *emph* AND [link](url) AND `code`"));
+        assert!(result.contains("- list item"));
+        assert!(result.contains("> quote"));
+        assert!(result.contains("| cell |"));
+    }
+
+    // Test that inline containers can be descended into, but never links or code
+    #[test]
+    fn test_inline_containers() {
+        let result = capitalize_in(&Containers::from_iter([
+            Container::Heading,
+            Container::Emph,
+        ]));
+
+        assert!(result.contains("# HEADING"));
+        assert!(result.contains("PARAGRAPH WITH *EMPH* AND [link](url) AND `code`"));
+        assert!(result.contains("code block"));
+    }
+
+    #[test]
+    fn test_extensions_gfm() {
+        let options = Extensions::from_iter([Extension::Gfm]).comrak_options();
+        assert!(options.extension.strikethrough);
+        assert!(options.extension.tagfilter);
+        assert!(options.extension.table);
+        assert!(options.extension.autolink);
+        assert!(options.extension.tasklist);
+        assert!(options.extension.footnotes);
+        assert!(!options.extension.superscript);
+        assert!(!options.extension.description_lists);
+
+        let options = Extensions::from_iter([Extension::Table]).comrak_options();
+        assert!(options.extension.table);
+        assert!(!options.extension.autolink);
+
+        let options = Extensions::default().comrak_options();
+        assert!(!options.extension.table);
+    }
+
+    // Test that text in autolinks, footnote definitions and strikethroughs is left
+    // alone, while task list items are linked like any other list item
+    #[test]
+    fn test_gfm_nodes() {
+        let markdown = "
+- [ ] task item
+
+see www.example.com and ~~struck~~ text[^1]
+
+[^1]: footnote text
+";
+        let arena = Arena::new();
+        let options = Extensions::from_iter([Extension::Gfm]).comrak_options();
+        let root = parse_document(&arena, markdown, &options);
+
+        alter_text(
+            &arena,
+            root,
+            &Containers::default(),
+            &mut |arena, text: &str, _| capitalize(arena, text),
+        );
+
+        let mut buf = vec![];
+        comrak::format_commonmark(root, &options, &mut buf).unwrap();
         let result = String::from_utf8(buf).unwrap();
 
-        assert!(result.contains("This is a paragraph with [Google](https://www.google.com)."));
-        assert!(result.contains("Another paragraph."));
+        assert!(result.contains("- [ ] TASK ITEM"));
+        assert!(
+            result.contains("SEE [www.example.com](http://www.example.com) AND ~struck~ TEXT[^1]")
+        );
+        assert!(result.contains("    footnote text"));
+    }
+
+    #[test]
+    fn test_link_urls() {
+        let markdown = "See [one](first) and ![image](pic.png).\n\n- [two](/docs/second/ \"title\")\n\n<https://example.com>";
+        let arena = Arena::new();
+        let root = parse_document(&arena, markdown, &ComrakOptions::default());
+
+        assert_eq!(
+            link_urls(root),
+            vec!["first", "/docs/second/", "https://example.com"]
+        );
+    }
+
+    #[test]
+    fn test_off_regions() {
+        let markdown = "one
+
+<!-- mdlinker:off -->
+
+two
+
+> three <!-- mdlinker:on --> four
+
+five <!-- mdlinker:off --> six
+
+- seven
+<!-- mdlinker:on -->
+
+eight";
+
+        let arena = Arena::new();
+        let root = parse_document(&arena, markdown, &ComrakOptions::default());
+        let mut seen = vec![];
+        alter_text(
+            &arena,
+            root,
+            &Containers::default(),
+            &mut |_, text: &str, _| {
+                seen.push(text.trim().to_string());
+                None
+            },
+        );
+        assert_eq!(seen, vec!["one", "four", "five", "eight"]);
     }
 }
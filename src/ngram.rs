@@ -1,5 +1,3 @@
-use std::collections::VecDeque;
-
 fn addr_of(s: &str) -> usize {
     s.as_ptr() as usize
 }
@@ -11,78 +9,10 @@ pub fn positioned<'a, I: Iterator<Item = &'a str>>(
     iter.map(move |sub| (addr_of(sub) - addr_of(input), sub))
 }
 
-pub fn ngram<T: Copy, I: Iterator<Item = T>>(
-    mut iter: I,
-    n: usize,
-) -> impl Iterator<Item = Vec<T>> {
-    if n == 0 {
-        panic!("invalid length: {n}")
-    }
-    let mut buf = VecDeque::with_capacity(n + 1);
-    while buf.len() < (n - 1) {
-        match iter.next() {
-            None => break,
-            Some(v) => buf.push_back(v),
-        }
-    }
-
-    iter.map(move |v| {
-        buf.push_back(v);
-        while buf.len() > n {
-            buf.pop_front();
-        }
-
-        let res: Vec<_> = buf.iter().cloned().collect();
-        res
-    })
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_ngram_empty() {
-        let input: Vec<i32> = vec![];
-        let output: Vec<Vec<i32>> = vec![];
-        assert_eq!(ngram(input.into_iter(), 2).collect::<Vec<_>>(), output);
-    }
-
-    #[test]
-    fn test_ngram_single() {
-        let input = vec![1];
-        let output: Vec<Vec<i32>> = vec![];
-        assert_eq!(ngram(input.into_iter(), 2).collect::<Vec<_>>(), output);
-    }
-
-    #[test]
-    fn test_ngram_double() {
-        let input = vec![1, 2];
-        let output = vec![vec![1, 2]];
-        assert_eq!(ngram(input.into_iter(), 2).collect::<Vec<_>>(), output);
-    }
-
-    #[test]
-    fn test_ngram_multiple() {
-        let input = vec![1, 2, 3, 4, 5];
-        let output = vec![vec![1, 2], vec![2, 3], vec![3, 4], vec![4, 5]];
-        assert_eq!(ngram(input.into_iter(), 2).collect::<Vec<_>>(), output);
-    }
-
-    #[test]
-    fn test_ngram_trigrams() {
-        let input = vec![1, 2, 3, 4, 5];
-        let output = vec![vec![1, 2, 3], vec![2, 3, 4], vec![3, 4, 5]];
-        assert_eq!(ngram(input.into_iter(), 3).collect::<Vec<_>>(), output);
-    }
-
-    #[test]
-    #[should_panic(expected = "invalid length: 0")]
-    fn test_ngram_zero() {
-        let input = vec![1, 2, 3, 4, 5];
-        let _ = ngram(input.into_iter(), 0).collect::<Vec<_>>();
-    }
-
     // use split whitespace as the postioned function
     fn positioned_split_whitespace(input: &str) -> impl Iterator<Item = (usize, &str)> {
         positioned(input, input.split_whitespace())
@@ -0,0 +1,446 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::collections::{HashMap, HashSet};
+
+use crate::{budget, document, relevance};
+
+/// weight of each word of a match, against the relevance of its target: one
+/// more word outweighs two shared keywords
+const WORD_WEIGHT: usize = 8;
+
+/// A keyword occurrence in a paragraph that could be linked.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord)]
+pub struct Candidate {
+    /// the byte offset of the occurrence in the paragraph
+    pub start: usize,
+    /// the byte length of the occurrence
+    pub length: usize,
+    pub keyword: String,
+    pub slug: String,
+    pub url: String,
+    /// how well the target fits the markdown
+    pub relevance: relevance::Score,
+    /// the pages the keyword links to, of which the most relevant was chosen
+    pub targets: usize,
+}
+
+impl Candidate {
+    pub fn end(&self) -> usize {
+        self.start + self.length
+    }
+
+    /// the words of the keyword, which is normalized to single spaces
+    pub fn words(&self) -> usize {
+        self.keyword.split(' ').count()
+    }
+
+    /// how strongly this link is preferred over others in the same
+    /// paragraph: matches of more words are more specific and rank higher,
+    /// and so do more relevant targets
+    pub fn score(&self) -> usize {
+        WORD_WEIGHT * self.words() + self.relevance.total() as usize
+    }
+}
+
+/// The paragraph a policy selects links for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Paragraph<'a> {
+    pub text: &'a str,
+    /// the line of the markdown the paragraph starts on, from 1
+    pub line: usize,
+    /// the number of headings before the paragraph
+    pub section: usize,
+}
+
+/// Decides which candidates become links. A policy sees the paragraphs of a
+/// markdown one by one, in order, after `start` is called for the markdown,
+/// and may keep state across them.
+pub trait LinkPolicy {
+    /// Called before the first paragraph of every markdown.
+    fn start(&mut self, _doc: &document::Document) {}
+
+    /// Returns the candidates to link, which must not overlap. Candidates
+    /// are given in order of their position, and may overlap each other.
+    fn select(
+        &mut self,
+        doc: &document::Document,
+        paragraph: &Paragraph,
+        candidates: &[Candidate],
+    ) -> Vec<Candidate>;
+}
+
+/// Picks the non-overlapping candidates with the highest total score, by
+/// weighted interval scheduling. The picks are returned in order of their
+/// position, and of equally good picks the one ending first wins.
+pub fn schedule(mut candidates: Vec<Candidate>) -> Vec<Candidate> {
+    candidates.sort_by(|a, b| a.end().cmp(&b.end()).then_with(|| a.start.cmp(&b.start)));
+
+    // best[i] is the highest total of the first i candidates, and taken[i]
+    // whether it includes candidate i - 1
+    let mut best = vec![0; candidates.len() + 1];
+    let mut taken = vec![false; candidates.len() + 1];
+    // the number of candidates ending before each one starts
+    let mut before = vec![0; candidates.len()];
+    for (i, candidate) in candidates.iter().enumerate() {
+        before[i] = candidates[..i].partition_point(|c| c.end() <= candidate.start);
+        let with = candidate.score() + best[before[i]];
+        if with > best[i] {
+            best[i + 1] = with;
+            taken[i + 1] = true;
+        } else {
+            best[i + 1] = best[i];
+        }
+    }
+
+    let mut picked = Vec::new();
+    let mut i = candidates.len();
+    while i > 0 {
+        if taken[i] {
+            picked.push(i - 1);
+            i = before[i - 1];
+        } else {
+            i -= 1;
+        }
+    }
+
+    let mut candidates: Vec<_> = candidates.into_iter().map(Some).collect();
+    picked
+        .into_iter()
+        .rev()
+        .filter_map(|i| candidates[i].take())
+        .collect()
+}
+
+/// Like `schedule`, but links each page, and uses each keyword, at most once.
+/// When the best picks repeat one, its later occurrence is left out and the
+/// rest are scheduled again, so that it does not crowd out other links.
+pub fn schedule_distinct(mut candidates: Vec<Candidate>) -> Vec<Candidate> {
+    loop {
+        let picked = schedule(candidates.clone());
+        let repeated = {
+            let mut seen_urls = HashSet::new();
+            let mut seen_keywords = HashSet::new();
+            picked.iter().position(|c| {
+                let new_url = seen_urls.insert(c.url.as_str());
+                let new_keyword = seen_keywords.insert(c.keyword.as_str());
+                !(new_url && new_keyword)
+            })
+        };
+        match repeated {
+            Some(i) => candidates.retain(|c| *c != picked[i]),
+            None => return picked,
+        }
+    }
+}
+
+/// The policy mdlinker links with: the best non-overlapping candidates of
+/// each paragraph, within the link budget, each page and keyword once per
+/// paragraph.
+#[derive(Debug, Clone)]
+pub struct DefaultPolicy {
+    budget: budget::Budget,
+    seed: Option<u64>,
+    /// the budget with the overrides of the current markdown
+    current: budget::Budget,
+    /// samples among the candidates instead of ranking them, if seeded
+    rng: Option<StdRng>,
+    /// links so far to each url, in the markdown or, when relinking
+    /// sections, in the current section
+    added_url: HashMap<String, usize>,
+    section: usize,
+    links: usize,
+}
+
+impl DefaultPolicy {
+    /// A policy that keeps the links of every markdown within the budget,
+    /// unless the markdown sets its own limits, and that samples among the
+    /// candidates with the seed, if one is given, instead of ranking them.
+    /// The seed only orders the links: which target a keyword links to is
+    /// sampled with `write::Options::seed`, which should be set to the same
+    /// seed for the output of the command line tool.
+    pub fn new(budget: budget::Budget, seed: Option<u64>) -> Self {
+        Self {
+            budget,
+            seed,
+            current: budget,
+            rng: seed.map(StdRng::seed_from_u64),
+            added_url: HashMap::new(),
+            section: 0,
+            links: 0,
+        }
+    }
+
+    fn is_spent(&self, candidate: &Candidate) -> bool {
+        let count = self.added_url.get(&candidate.url).copied();
+        !self.current.allows_target(count.unwrap_or_default())
+    }
+}
+
+impl Default for DefaultPolicy {
+    fn default() -> Self {
+        Self::new(Default::default(), None)
+    }
+}
+
+impl LinkPolicy for DefaultPolicy {
+    fn start(&mut self, doc: &document::Document) {
+        *self = Self {
+            current: self.budget.with(&doc.front_matter.budget),
+            ..Self::new(self.budget, self.seed)
+        };
+    }
+
+    fn select(
+        &mut self,
+        _doc: &document::Document,
+        paragraph: &Paragraph,
+        candidates: &[Candidate],
+    ) -> Vec<Candidate> {
+        let budget = self.current;
+        if budget.relink_sections && paragraph.section != self.section {
+            self.section = paragraph.section;
+            self.added_url.clear();
+        }
+
+        let candidates = candidates
+            .iter()
+            .filter(|c| !self.is_spent(c))
+            .cloned()
+            .collect();
+
+        // each page is linked, and each keyword used, once per paragraph
+        let mut res = schedule_distinct(candidates);
+
+        match self.rng.as_mut() {
+            Some(rng) => res.shuffle(rng),
+            None => res.sort_by(|a, b| {
+                b.score()
+                    .cmp(&a.score())
+                    .then_with(|| a.url.cmp(&b.url))
+                    .then_with(|| a.start.cmp(&b.start))
+            }),
+        }
+        // the best candidates are kept while they fit the budget
+        let room = budget.paragraph_room(self.links);
+        let mut kept: Vec<Candidate> = Vec::new();
+        for candidate in res {
+            if kept.len() >= room {
+                break;
+            }
+            let span = (candidate.start, candidate.end());
+            if kept
+                .iter()
+                .all(|k| budget.allows_gap(paragraph.text, (k.start, k.end()), span))
+            {
+                kept.push(candidate);
+            }
+        }
+
+        kept.sort();
+        for c in &kept {
+            *self.added_url.entry(c.url.clone()).or_default() += 1;
+        }
+        self.links += kept.len();
+        kept
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(start: usize, length: usize, slug: &str) -> Candidate {
+        Candidate {
+            start,
+            length,
+            keyword: slug.to_string(),
+            slug: slug.to_string(),
+            url: slug.to_string(),
+            relevance: Default::default(),
+            targets: 1,
+        }
+    }
+
+    #[test]
+    fn test_schedule() {
+        let slugs = |candidates: Vec<Candidate>| -> Vec<String> {
+            schedule(candidates).into_iter().map(|c| c.slug).collect()
+        };
+        let candidate = |start, keyword: &str| Candidate {
+            keyword: keyword.to_string(),
+            ..candidate(start, keyword.len(), keyword)
+        };
+
+        // an early short match does not block a longer one
+        assert_eq!(
+            slugs(vec![
+                candidate(0, "async rust"),
+                candidate(6, "rust runtime tokio")
+            ]),
+            vec!["rust runtime tokio"]
+        );
+        // two matches beat one that overlaps both if they have more words
+        assert_eq!(
+            slugs(vec![
+                candidate(0, "a b"),
+                candidate(2, "b c"),
+                candidate(4, "c d"),
+            ]),
+            vec!["a b", "c d"]
+        );
+        // the earlier of equally good matches wins
+        assert_eq!(
+            slugs(vec![candidate(4, "later"), candidate(0, "earlier")]),
+            vec!["earlier"]
+        );
+        assert!(schedule(vec![]).is_empty());
+    }
+
+    #[test]
+    fn test_schedule_words_against_relevance() {
+        let slugs = |candidates: Vec<Candidate>| -> Vec<String> {
+            schedule(candidates).into_iter().map(|c| c.slug).collect()
+        };
+        let candidate = |start, keyword: &str, keywords| Candidate {
+            keyword: keyword.to_string(),
+            relevance: relevance::Score {
+                keywords,
+                ..Default::default()
+            },
+            ..candidate(start, keyword.len(), keyword)
+        };
+
+        // a word more outweighs a shared keyword of the shorter match
+        assert_eq!(
+            slugs(vec![
+                candidate(0, "tokio", 1),
+                candidate(0, "tokio runtime", 0)
+            ]),
+            vec!["tokio runtime"]
+        );
+        // but not three, even though the longer match has more bytes
+        assert_eq!(
+            slugs(vec![
+                candidate(0, "tokio", 3),
+                candidate(0, "tokio runtime", 0)
+            ]),
+            vec!["tokio"]
+        );
+        // the more relevant of two matches as long wins, whatever their bytes
+        assert_eq!(
+            slugs(vec![candidate(0, "rt", 1), candidate(0, "runtime", 0)]),
+            vec!["rt"]
+        );
+        // a three-word match beats two overlapping words, unless they are
+        // relevant enough
+        assert_eq!(
+            slugs(vec![
+                candidate(0, "async", 0),
+                candidate(0, "async rust runtime", 0),
+                candidate(11, "runtime", 0),
+            ]),
+            vec!["async rust runtime"]
+        );
+        assert_eq!(
+            slugs(vec![
+                candidate(0, "async", 2),
+                candidate(0, "async rust runtime", 0),
+                candidate(11, "runtime", 2),
+            ]),
+            vec!["async", "runtime"]
+        );
+    }
+
+    #[test]
+    fn test_schedule_distinct() {
+        // rust runtime and async rust runtime
+        let candidate = |start, keyword: &str, slug: &str| Candidate {
+            keyword: keyword.to_string(),
+            ..candidate(start, keyword.len(), slug)
+        };
+        let candidates = vec![
+            candidate(0, "rust runtime", "runtime"),
+            candidate(17, "async rust", "rust"),
+            candidate(23, "rust runtime", "runtime"),
+        ];
+        let slugs: Vec<_> = schedule_distinct(candidates)
+            .into_iter()
+            .map(|c| (c.start, c.slug))
+            .collect();
+        assert_eq!(
+            slugs,
+            vec![(0, "runtime".to_string()), (17, "rust".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_default_policy_state() {
+        let content = "---\nslug: source\nmax_links_per_document: 2\n---\n";
+        let doc = document::Document::parse(
+            content,
+            std::path::Path::new("source.md"),
+            &Default::default(),
+        )
+        .unwrap();
+        let paragraph = Paragraph {
+            text: "alpha beta gamma",
+            line: 1,
+            section: 0,
+        };
+        let candidates = [
+            candidate(0, 5, "alpha"),
+            candidate(6, 4, "beta"),
+            candidate(11, 5, "gamma"),
+        ];
+
+        let mut policy = DefaultPolicy::default();
+        policy.start(&doc);
+        assert_eq!(
+            policy.select(&doc, &paragraph, &candidates),
+            vec![candidates[0].clone(), candidates[1].clone()]
+        );
+        // the budget of the markdown is spent
+        assert!(policy.select(&doc, &paragraph, &candidates).is_empty());
+
+        // and is restored for the next one
+        policy.start(&doc);
+        assert_eq!(policy.select(&doc, &paragraph, &candidates).len(), 2);
+    }
+
+    #[test]
+    fn test_default_policy_per_target() {
+        let doc = document::Document::parse(
+            "---\nslug: source\n---\n",
+            std::path::Path::new("source.md"),
+            &Default::default(),
+        )
+        .unwrap();
+        let paragraph = Paragraph {
+            text: "tokio",
+            line: 1,
+            section: 0,
+        };
+        let candidate = |slug| Candidate {
+            keyword: "tokio".to_string(),
+            ..candidate(0, 5, slug)
+        };
+
+        let mut policy = DefaultPolicy::default();
+        policy.start(&doc);
+        assert_eq!(
+            policy.select(&doc, &paragraph, &[candidate("tokio")]).len(),
+            1
+        );
+        // the page is linked once, but the keyword may still link elsewhere
+        assert!(policy
+            .select(&doc, &paragraph, &[candidate("tokio")])
+            .is_empty());
+        assert_eq!(
+            policy
+                .select(&doc, &paragraph, &[candidate("tokio-guide")])
+                .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use std::cell::{OnceCell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Component, Path};
+
+use crate::{keyword, tokenize};
+
+/// weight of each target keyword that the source shares
+const KEYWORD_WEIGHT: u32 = 4;
+/// weight of each leading folder the source and the target share
+const DIRECTORY_WEIGHT: u32 = 2;
+/// weight of the share of words the source and the target have in common,
+/// per 10%
+const CONTENT_WEIGHT: u32 = 1;
+
+/// The words of a text that say something about its topic: lowercased, and
+/// at least four letters long.
+pub(crate) fn vocabulary(text: &str) -> HashSet<String> {
+    tokenize::tokenize(text)
+        .map(|(_, word)| word.to_lowercase())
+        .filter(|word| word.chars().count() >= 4 && word.chars().all(char::is_alphanumeric))
+        .collect()
+}
+
+/// The markdown links are inserted into, as far as ranking the targets of
+/// its keywords goes. What it is about is only worked out once a target is
+/// scored, and each target is scored once per markdown.
+#[derive(Debug, Clone)]
+pub(crate) struct Source<'a> {
+    pub slug: &'a str,
+    pub path: &'a Path,
+    terms: Vec<String>,
+    markdown: &'a str,
+    /// its own keywords and the keywords that occur in it
+    keywords: OnceCell<HashSet<String>>,
+    words: OnceCell<HashSet<String>>,
+    scores: RefCell<HashMap<String, Score>>,
+}
+
+impl<'a> Source<'a> {
+    pub fn new(slug: &'a str, path: &'a Path, terms: &[&str], markdown: &'a str) -> Self {
+        Self {
+            slug: keyword::normalize_slug(slug),
+            path,
+            terms: terms.iter().map(|term| keyword::normalize(term)).collect(),
+            markdown,
+            keywords: OnceCell::new(),
+            words: OnceCell::new(),
+            scores: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Scores how well the target with `slug` fits this markdown.
+    pub fn score(&self, keywords: &keyword::Keywords, slug: &str) -> Score {
+        if let Some(score) = self.scores.borrow().get(slug) {
+            return *score;
+        }
+        let score = self.compute(keywords, slug);
+        self.scores.borrow_mut().insert(slug.to_owned(), score);
+        score
+    }
+
+    fn compute(&self, keywords: &keyword::Keywords, slug: &str) -> Score {
+        let own = self.keywords.get_or_init(|| {
+            let found = keywords.find(tokenize::tokenize(self.markdown));
+            let found = found.into_iter().map(|found| found.keyword.to_string());
+            self.terms.iter().cloned().chain(found).collect()
+        });
+        let shared_keywords = keywords
+            .keywords_of(slug)
+            .filter(|keyword| own.contains(*keyword))
+            .count();
+
+        let directory = match keywords.path(slug) {
+            Some(target) => shared_folders(self.path, target),
+            None => 0,
+        };
+
+        let own = self.words.get_or_init(|| vocabulary(self.markdown));
+        let content = match keywords.words(slug) {
+            Some(words) if !words.is_empty() || !own.is_empty() => {
+                let shared = words.intersection(own).count();
+                let all = words.union(own).count();
+                (shared * 100 / all) as u32
+            }
+            _ => 0,
+        };
+
+        Score {
+            keywords: shared_keywords as u32,
+            directory: directory as u32,
+            content,
+        }
+    }
+}
+
+/// the number of leading folders two markdowns have in common
+fn shared_folders(source: &Path, target: &Path) -> usize {
+    fn folders(path: &Path) -> Vec<Component<'_>> {
+        path.parent()
+            .into_iter()
+            .flat_map(Path::components)
+            .filter(|c| !matches!(c, Component::CurDir))
+            .collect()
+    }
+    folders(source)
+        .iter()
+        .zip(&folders(target))
+        .take_while(|(a, b)| a == b)
+        .count()
+}
+
+/// How well a target fits the markdown linking to it. Targets are ranked by
+/// `total`; ties go to the slug first in sorted order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Score {
+    /// the target keywords the source shares
+    pub keywords: u32,
+    /// the leading folders the source and the target share
+    pub directory: u32,
+    /// the words the source and the target have in common, in percent of
+    /// the words either has
+    pub content: u32,
+}
+
+impl Score {
+    pub fn total(&self) -> u32 {
+        KEYWORD_WEIGHT * self.keywords
+            + DIRECTORY_WEIGHT * self.directory
+            + CONTENT_WEIGHT * (self.content / 10)
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({} shared keywords x{KEYWORD_WEIGHT}, {} shared folders x{DIRECTORY_WEIGHT}, \
+             {}% shared words x{CONTENT_WEIGHT} per 10%)",
+            self.total(),
+            self.keywords,
+            self.directory,
+            self.content,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index() -> keyword::Keywords {
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert(" ci ", &["pipeline", "continuous integration", "build"]);
+        keywords.set_path("ci", Path::new("docs/devops/ci.md"));
+        keywords.set_words(
+            "ci ",
+            "Every commit runs the build pipeline on the runners.",
+        );
+        keywords.insert("data-pipeline", &["pipeline", "etl", "warehouse"]);
+        keywords.set_path("data-pipeline", Path::new("docs/data/pipeline.md"));
+        keywords.set_words(
+            "data-pipeline",
+            "Rows are loaded into the warehouse nightly.",
+        );
+        keywords
+    }
+
+    #[test]
+    fn test_score() {
+        let keywords = index();
+        let source = Source::new(
+            " deploy ",
+            Path::new("docs/devops/deploy.md"),
+            &["deploy"],
+            "A deploy starts once the build pipeline has finished on the runners.",
+        );
+
+        assert_eq!(source.slug, "deploy");
+        // slugs are the same with or without surrounding whitespace
+        assert_eq!(
+            source.score(&keywords, " ci"),
+            source.score(&keywords, "ci")
+        );
+        let ci = source.score(&keywords, "ci");
+        assert_eq!(ci.keywords, 2);
+        assert_eq!(ci.directory, 2);
+        assert!(ci.content > 0);
+
+        let data = source.score(&keywords, "data-pipeline");
+        assert_eq!(data.keywords, 1);
+        assert_eq!(data.directory, 1);
+        assert_eq!(data.content, 0);
+        assert!(ci.total() > data.total());
+        assert_eq!(
+            data.to_string(),
+            "6 (1 shared keywords x4, 1 shared folders x2, 0% shared words x1 per 10%)"
+        );
+    }
+
+    #[test]
+    fn test_vocabulary() {
+        assert_eq!(
+            vocabulary("The Build and the build's runners, v1.2"),
+            HashSet::from(["build", "runners"].map(String::from))
+        );
+    }
+}
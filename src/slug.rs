@@ -0,0 +1,124 @@
+use std::path::{Component, Path, PathBuf};
+
+/// How the slug of a markdown without a `slug` in its front matter is
+/// derived from its path.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SlugStrategy {
+    /// the file name without its extension
+    #[default]
+    Stem,
+    /// the path relative to the glob without its extension
+    Path,
+    /// like path, but `index.md` and `_index.md` take the path of their folder
+    Directory,
+}
+
+/// file stems that stand for the folder they are in
+const INDEX_STEMS: [&str; 2] = ["index", "_index"];
+
+/// Derives slugs from the paths of markdowns.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Slugger {
+    strategy: SlugStrategy,
+    /// the folder paths are taken relative to
+    root: PathBuf,
+}
+
+impl Slugger {
+    /// Slugs are taken relative to the part of the glob before its first
+    /// wildcard, so `docs/**/*.md` puts `docs/a/b.md` at `a/b`.
+    pub fn new(strategy: SlugStrategy, glob: &str) -> Self {
+        let root = Path::new(glob)
+            .components()
+            .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+            .filter(|c| *c != Component::CurDir)
+            .collect();
+        Self { strategy, root }
+    }
+
+    pub fn derive(&self, path: &Path) -> Option<String> {
+        let stem = path.file_stem()?.to_string_lossy();
+        if self.strategy == SlugStrategy::Stem {
+            return Some(stem.into_owned());
+        }
+
+        let path: PathBuf = path
+            .components()
+            .filter(|c| *c != Component::CurDir)
+            .collect();
+        let relative = path.strip_prefix(&self.root).unwrap_or_else(|_| {
+            log::warn!(
+                "{} is not in {}, so its slug is taken from its whole path",
+                path.to_string_lossy(),
+                self.root.to_string_lossy()
+            );
+            &path
+        });
+        let mut parts: Vec<_> = relative
+            .parent()
+            .into_iter()
+            .flat_map(Path::components)
+            .filter_map(|c| match c {
+                Component::Normal(part) => Some(part.to_string_lossy()),
+                _ => None,
+            })
+            .collect();
+        let is_index = INDEX_STEMS.contains(&stem.as_ref());
+        if !(self.strategy == SlugStrategy::Directory && is_index && !parts.is_empty()) {
+            parts.push(stem);
+        }
+        Some(parts.join("/"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_root() {
+        assert_eq!(
+            Slugger::new(SlugStrategy::Path, "docs/**/*.md").root,
+            Path::new("docs")
+        );
+        assert_eq!(
+            Slugger::new(SlugStrategy::Path, "./docs/*.md").root,
+            Path::new("docs")
+        );
+        assert_eq!(Slugger::new(SlugStrategy::Path, "*.md").root, Path::new(""));
+    }
+
+    #[test]
+    fn test_derive() {
+        let cases = [
+            ("docs/guide/setup.md", "setup", "guide/setup", "guide/setup"),
+            ("docs/guide/index.md", "index", "guide/index", "guide"),
+            ("docs/guide/_index.md", "_index", "guide/_index", "guide"),
+            ("docs/index.md", "index", "index", "index"),
+            ("other/page.md", "page", "other/page", "other/page"),
+        ];
+        for (path, stem, relative, directory) in cases {
+            let path = Path::new(path);
+            for (strategy, expected) in [
+                (SlugStrategy::Stem, stem),
+                (SlugStrategy::Path, relative),
+                (SlugStrategy::Directory, directory),
+            ] {
+                let slugger = Slugger::new(strategy, "docs/**/*.md");
+                assert_eq!(slugger.derive(path).as_deref(), Some(expected));
+            }
+        }
+    }
+
+    #[test]
+    fn test_derive_dot_prefixed_glob() {
+        // glob yields the matches of ./docs/**/*.md without the ./
+        for strategy in [SlugStrategy::Path, SlugStrategy::Directory] {
+            let slugger = Slugger::new(strategy, "./docs/**/*.md");
+            for path in ["docs/sub/a.md", "./docs/sub/a.md"] {
+                assert_eq!(slugger.derive(Path::new(path)).as_deref(), Some("sub/a"));
+            }
+        }
+    }
+}
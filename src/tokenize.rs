@@ -0,0 +1,93 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::ngram;
+
+const POSSESSIVE_SUFFIXES: [&str; 2] = ["'s", "\u{2019}s"];
+
+/// Splits a possessive word such as "matter's" into "matter" and "'s".
+fn split_possessive(word: &str) -> impl Iterator<Item = &str> {
+    let stem = POSSESSIVE_SUFFIXES
+        .iter()
+        .find_map(|suffix| word.strip_suffix(suffix))
+        .filter(|stem| !stem.is_empty());
+
+    let (head, tail) = match stem {
+        Some(stem) => word.split_at(stem.len()),
+        None => (word, ""),
+    };
+    [head, tail].into_iter().filter(|s| !s.is_empty())
+}
+
+/// Splits the input into tokens on Unicode word boundaries, reporting each
+/// token with its byte offset into the input.
+///
+/// Whitespace is dropped, but punctuation is kept as tokens of its own so
+/// that a keyword only matches the words it spells out: "(front matter)."
+/// yields `(`, `front`, `matter`, `)` and `.`.
+pub fn tokenize(input: &str) -> impl Iterator<Item = (usize, &str)> {
+    ngram::positioned(
+        input,
+        input
+            .split_word_bounds()
+            .filter(|s| !s.trim().is_empty())
+            .flat_map(split_possessive),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(input: &str) -> Vec<(usize, &str)> {
+        tokenize(input).collect()
+    }
+
+    #[test]
+    fn test_tokenize_whitespace() {
+        assert_eq!(
+            tokens("  see   the\tdocs "),
+            vec![(2, "see"), (8, "the"), (12, "docs")]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_punctuation() {
+        assert_eq!(
+            tokens("(front matter)."),
+            vec![(0, "("), (1, "front"), (7, "matter"), (13, ")"), (14, ".")]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_possessive() {
+        assert_eq!(
+            tokens("front matter's and it\u{2019}s"),
+            vec![
+                (0, "front"),
+                (6, "matter"),
+                (12, "'s"),
+                (15, "and"),
+                (19, "it"),
+                (21, "\u{2019}s")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_keeps_inner_punctuation() {
+        assert_eq!(tokens("node.js can't"), vec![(0, "node.js"), (8, "can't")]);
+    }
+
+    #[test]
+    fn test_tokenize_unicode() {
+        assert_eq!(
+            tokens("Größe «Überblick»"),
+            vec![(0, "Größe"), (8, "«"), (10, "Überblick"), (20, "»")]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_empty() {
+        assert_eq!(tokens(""), vec![]);
+    }
+}
@@ -0,0 +1,208 @@
+use std::path::{Component, Path};
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum Error {
+    #[error("unknown placeholder {{{0}}} in url template")]
+    UnknownPlaceholder(String),
+    #[error("unclosed placeholder in url template: {0}")]
+    UnclosedPlaceholder(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Placeholder {
+    /// the base url
+    Base,
+    /// the slug of the target
+    Slug,
+    /// the path of the target as it was globbed
+    Path,
+    /// the path of the target relative to the linking markdown
+    RelPath,
+    /// the relative path without the file extension
+    RelStem,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+/// Builds link urls from a template such as `{base}/{slug}/`,
+/// `/docs/{slug}.html` or `{relstem}.html`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UrlTemplate {
+    segments: Vec<Segment>,
+    base: String,
+}
+
+impl Default for UrlTemplate {
+    fn default() -> Self {
+        Self {
+            segments: vec![Segment::Placeholder(Placeholder::Slug)],
+            base: String::new(),
+        }
+    }
+}
+
+impl UrlTemplate {
+    pub fn new(template: &str, base: &str) -> Result<Self, Error> {
+        let mut segments = Vec::new();
+        let mut rest = template;
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                segments.push(Segment::Literal(rest[..open].to_string()));
+            }
+            let Some(close) = rest[open..].find('}') else {
+                return Err(Error::UnclosedPlaceholder(template.to_string()));
+            };
+            let placeholder = match &rest[open + 1..open + close] {
+                "base" => Placeholder::Base,
+                "slug" => Placeholder::Slug,
+                "path" => Placeholder::Path,
+                "relpath" => Placeholder::RelPath,
+                "relstem" => Placeholder::RelStem,
+                other => return Err(Error::UnknownPlaceholder(other.to_string())),
+            };
+            segments.push(Segment::Placeholder(placeholder));
+            rest = &rest[open + close + 1..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_string()));
+        }
+
+        Ok(Self {
+            segments,
+            base: base.trim_end_matches('/').to_string(),
+        })
+    }
+
+    /// the base url, without a trailing `/`
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+
+    /// Renders the url linking from the markdown at `source` to the one with
+    /// `slug` at `target`. Returns `None` if the template needs the target's
+    /// path but it is not known.
+    pub fn render(&self, slug: &str, source: &Path, target: Option<&Path>) -> Option<String> {
+        let mut url = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(literal) => url.push_str(literal),
+                Segment::Placeholder(Placeholder::Base) => url.push_str(&self.base),
+                Segment::Placeholder(Placeholder::Slug) => url.push_str(slug),
+                Segment::Placeholder(Placeholder::Path) => {
+                    url.push_str(&join(target?.components()))
+                }
+                Segment::Placeholder(Placeholder::RelPath) => {
+                    url.push_str(&relative(source, target?))
+                }
+                Segment::Placeholder(Placeholder::RelStem) => {
+                    let target = target?;
+                    url.push_str(&relative(source, &target.with_extension("")))
+                }
+            }
+        }
+        Some(url)
+    }
+}
+
+fn join<'a>(components: impl Iterator<Item = Component<'a>>) -> String {
+    let parts: Vec<_> = components
+        .filter(|c| !matches!(c, Component::CurDir))
+        .map(|c| match c {
+            Component::RootDir => "".into(),
+            c => c.as_os_str().to_string_lossy(),
+        })
+        .collect();
+    parts.join("/")
+}
+
+/// The path of `target` relative to the folder containing `source`,
+/// always separated by `/`.
+fn relative(source: &Path, target: &Path) -> String {
+    fn clean(path: &Path) -> Vec<Component<'_>> {
+        path.components()
+            .filter(|c| !matches!(c, Component::CurDir))
+            .collect()
+    }
+    let from = clean(source.parent().unwrap_or(Path::new("")));
+    let to = clean(target);
+
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let ups = std::iter::repeat_n(Component::ParentDir, from.len() - common);
+    join(ups.chain(to[common..].iter().copied()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_is_slug() {
+        let template = UrlTemplate::default();
+        assert_eq!(
+            template.render("my-slug", Path::new("a.md"), None),
+            Some("my-slug".to_string())
+        );
+    }
+
+    #[test]
+    fn test_base_and_slug() {
+        let template = UrlTemplate::new("{base}/{slug}/", "https://example.com/").unwrap();
+        assert_eq!(
+            template.render("my-slug", Path::new("a.md"), None),
+            Some("https://example.com/my-slug/".to_string())
+        );
+
+        let template = UrlTemplate::new("/docs/{slug}.html", "").unwrap();
+        assert_eq!(
+            template.render("my-slug", Path::new("a.md"), None),
+            Some("/docs/my-slug.html".to_string())
+        );
+    }
+
+    #[test]
+    fn test_paths() {
+        let source = Path::new("./docs/guides/setup.md");
+        let target = Path::new("docs/reference/api/index.md");
+
+        let template = UrlTemplate::new("{relpath}", "").unwrap();
+        assert_eq!(
+            template.render("api", source, Some(target)),
+            Some("../reference/api/index.md".to_string())
+        );
+
+        let template = UrlTemplate::new("{relstem}.html", "").unwrap();
+        assert_eq!(
+            template.render("api", source, Some(target)),
+            Some("../reference/api/index.html".to_string())
+        );
+
+        let template = UrlTemplate::new("/{path}", "").unwrap();
+        assert_eq!(
+            template.render("api", source, Some(target)),
+            Some("/docs/reference/api/index.md".to_string())
+        );
+
+        let template = UrlTemplate::new("{relpath}", "").unwrap();
+        assert_eq!(
+            template.render("a", Path::new("docs/b.md"), Some(Path::new("docs/a.md"))),
+            Some("a.md".to_string())
+        );
+        assert_eq!(template.render("a", source, None), None);
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        assert_eq!(
+            UrlTemplate::new("/{slg}/", ""),
+            Err(Error::UnknownPlaceholder("slg".to_string()))
+        );
+        assert_eq!(
+            UrlTemplate::new("/{slug", ""),
+            Err(Error::UnclosedPlaceholder("/{slug".to_string()))
+        );
+    }
+}
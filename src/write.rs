@@ -1,146 +1,446 @@
-use rand::seq::SliceRandom;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::collections::HashSet;
 use std::fs::{self, create_dir_all};
 use std::io::Read;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 
-use crate::{document, keyword, markdown, ngram};
-
-#[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord)]
-struct Candidate {
-    start: usize,
-    length: usize,
-    keyword: String,
-    url: String,
-}
-
-impl Candidate {
-    fn end(&self) -> usize {
-        self.start + self.length
-    }
-
-    fn tuple(self) -> (usize, usize, String) {
-        (self.start, self.length, self.url)
-    }
-}
+pub use crate::markdown::{Container, Containers, Extension, Extensions, Marker};
+use crate::policy::{Candidate, LinkPolicy, Paragraph};
+pub use crate::url::UrlTemplate;
+use crate::{document, front_matter, keyword, markdown, relevance, tokenize, url};
 
 fn generate_candidates<R: Rng>(
     keywords: &keyword::Keywords,
     paragraph: &str,
-    doc: &document::Document,
-    rng: &mut R,
+    source: &relevance::Source,
+    urls: &url::UrlTemplate,
+    mut rng: Option<&mut R>,
 ) -> Vec<Candidate> {
     let mut candidates = Vec::new();
-    for n in (2..=4).rev() {
-        for grams in ngram::ngram(
-            ngram::positioned(paragraph, paragraph.split_whitespace()),
-            n,
-        ) {
-            let word = itertools::join(grams.iter().map(|s| s.1.to_lowercase()), " ");
-
-            if let Some(slugs) = keywords.get(&word) {
-                let slugs: Vec<_> = slugs
-                    .filter(|slug| *slug != doc.front_matter.slug)
-                    .collect();
-
-                if slugs.is_empty() {
-                    continue;
-                }
+    for found in keywords.find(tokenize::tokenize(paragraph)) {
+        let Some(slugs) = keywords.get(found.keyword) else {
+            continue;
+        };
+        let slugs: Vec<_> = slugs.filter(|slug| *slug != source.slug).collect();
+        if slugs.is_empty() {
+            continue;
+        }
 
-                let slug = slugs[rng.next_u64() as usize % slugs.len()];
-                if let Some((start, len)) =
-                    grams.first().and_then(|start| -> Option<(usize, usize)> {
-                        let end = grams.last()?;
-                        Some((start.0, end.0 + end.1.len() - start.0))
-                    })
-                {
-                    candidates.push(Candidate {
-                        start,
-                        length: len,
-                        keyword: word,
-                        url: slug.to_owned(),
-                    });
-                }
+        // a keyword of several pages links to the most relevant of them; a
+        // single target is scored too, as relevance also ranks the candidates
+        let mut ranked: Vec<(&str, relevance::Score)> = slugs
+            .iter()
+            .map(|slug| (*slug, source.score(keywords, slug)))
+            .collect();
+        let best = ranked.iter().map(|(_, score)| score.total()).max();
+        if slugs.len() > 1 {
+            for (slug, score) in &ranked {
+                log::debug!("{} -> {slug} scores {score}", found.keyword);
             }
         }
+        ranked.retain(|(_, score)| Some(score.total()) == best);
+
+        // slugs come back sorted, so without an rng the first one wins a tie
+        let (slug, relevance) = match rng.as_deref_mut() {
+            Some(rng) => ranked[rng.next_u64() as usize % ranked.len()],
+            None => ranked[0],
+        };
+        let Some(url) = urls.render(slug, source.path, keywords.path(slug)) else {
+            log::debug!("no path is known for {slug}, so it cannot be linked to");
+            continue;
+        };
+        candidates.push(Candidate {
+            start: found.start,
+            length: found.end - found.start,
+            keyword: found.keyword.to_owned(),
+            slug: slug.to_owned(),
+            url,
+            relevance,
+            targets: slugs.len(),
+        });
     }
     candidates
 }
 
-// TODO: returning the String is an additional allocation that is not necessary
-fn update_content(keywords: &keyword::Keywords, content: &str) -> anyhow::Result<String> {
-    // helper functions
-    let is_already_seen =
-        |candidate: &Candidate, seen_keywords: &HashSet<String>, seen_urls: &HashSet<String>| {
-            seen_urls.contains(&candidate.url) || seen_keywords.contains(&candidate.keyword)
+/// Resolves the urls of the links already in a markdown to the slugs they
+/// point at. Only relative urls, urls from the site root and urls under the
+/// base url are resolved; other absolute urls point elsewhere. A url resolves
+/// to the slug indexed from the markdown it names, as in `../guide.md`, or,
+/// without its query, fragment and file extension, to a slug it ends in, as
+/// in `/docs/guide/#setup`.
+fn linked_slugs(
+    keywords: &keyword::Keywords,
+    link_urls: &[String],
+    path: &Path,
+    urls: &url::UrlTemplate,
+) -> HashSet<String> {
+    let mut linked = HashSet::new();
+    for link_url in link_urls {
+        let base = urls.base();
+        let local = match link_url.strip_prefix(base) {
+            Some(rest) if !base.is_empty() && (rest.is_empty() || rest.starts_with('/')) => rest,
+            _ if link_url.starts_with("//") || has_scheme(link_url) => continue,
+            _ => link_url.as_str(),
         };
+        let local = local.split(['?', '#']).next().unwrap_or_default();
+        let local = local.trim_end_matches('/');
 
-    // set up
-    let doc = document::Document::parse(content)?;
-    let arena = comrak::Arena::new();
-    let ast = comrak::parse_document(&arena, doc.document, &Default::default());
-    let mut rng = rand::thread_rng(); // used to sample a candidate if there are multiple available
+        // a relative url names a markdown next to this one
+        if !local.is_empty() && !local.starts_with('/') {
+            let relative = path.parent().unwrap_or(Path::new("")).join(local);
+            if let Some(slug) = keywords.slug_at(&normalize_path(&relative)) {
+                linked.insert(slug.to_string());
+                continue;
+            }
+        }
+
+        let trimmed = match local.rsplit_once('.') {
+            Some((stem, extension)) if !extension.contains('/') => stem,
+            _ => local,
+        };
+        let trimmed = trimmed.trim_start_matches('/');
+        let suffixes = trimmed
+            .match_indices('/')
+            .map(|(i, _)| &trimmed[i + 1..])
+            .chain([trimmed]);
+        linked.extend(
+            suffixes
+                .filter(|slug| keywords.contains_slug(slug))
+                .map(str::to_string),
+        );
+    }
+    linked
+}
 
-    let mut added_url: HashSet<String> = HashSet::new();
-    let mut added_keyword: HashSet<String> = HashSet::new();
-    markdown::add_links(&arena, ast, |paragraph| {
-        let mut candidates = generate_candidates(keywords, paragraph, &doc, &mut rng);
+/// whether the url starts with a scheme such as `https:` or `mailto:`
+fn has_scheme(url: &str) -> bool {
+    let scheme = url.split(['/', '?', '#']).next().unwrap_or_default();
+    scheme.contains(':')
+}
 
-        if candidates.is_empty() {
-            return vec![];
+/// the path with `.` and `..` folders resolved
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
         }
+    }
+    normalized
+}
+
+/// Options controlling how candidate links are found and written. Which of
+/// them become links, and how many, is up to the `LinkPolicy` they are
+/// written with; `DefaultPolicy` takes the link budget.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Options {
+    /// sample among equally relevant targets of a keyword with this seed
+    /// instead of taking the first slug; `DefaultPolicy::new` takes its own
+    /// seed for ordering the links, so pass both the same one to get what
+    /// the command line tool does with `--seed`
+    pub seed: Option<u64>,
+    /// re-render the whole markdown with comrak instead of splicing the
+    /// links into the original source
+    pub reformat: bool,
+    /// how the url of a link is built from its target
+    pub urls: url::UrlTemplate,
+    /// the containers besides paragraphs that links may be inserted into
+    pub containers: markdown::Containers,
+    /// the comrak extensions markdowns are parsed and rendered with
+    pub extensions: markdown::Extensions,
+    /// how slugs and keywords are read from front matter
+    pub schema: document::Schema,
+    /// how inserted links are marked, so that later runs can replace them
+    pub marker: markdown::Marker,
+    /// only remove the marked links instead of linking
+    pub unlink: bool,
+}
 
-        candidates.sort_by(|a, b| match a.start.cmp(&b.start) {
-            std::cmp::Ordering::Equal => b.length.cmp(&a.length),
-            otherwise => otherwise,
+/// A link inserted into a markdown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    /// the line of the original markdown the link was inserted on, from 1
+    pub line: usize,
+    pub keyword: String,
+    pub slug: String,
+    pub url: String,
+    /// how the slug was chosen, if the keyword has several
+    pub relevance: Option<relevance::Score>,
+}
+
+/// An updated markdown together with the links that were inserted into it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Update {
+    pub content: String,
+    pub links: Vec<Link>,
+}
+
+/// Links a markdown, letting the policy decide which keyword occurrences
+/// become links.
+pub fn update_content(
+    keywords: &keyword::Keywords,
+    path: &Path,
+    content: &str,
+    options: &Options,
+    policy: &mut dyn LinkPolicy,
+) -> anyhow::Result<Update> {
+    // links inserted by an earlier run are recomputed from scratch
+    let content = strip_content(content, options);
+    if options.unlink {
+        return Ok(Update {
+            content,
+            links: vec![],
         });
+    }
+    link_content(keywords, path, &content, options, policy)
+}
 
-        let mut res: Vec<Candidate> = Vec::new();
+/// Removes the links marked by an earlier run from the markdown below the
+/// front matter.
+fn strip_content(content: &str, options: &Options) -> String {
+    if options.marker == markdown::Marker::None {
+        return content.to_owned();
+    }
+    let Some((_, _, markdown)) = front_matter::split_front_matter(content) else {
+        return content.to_owned();
+    };
+    let header = &content[..content.len() - markdown.len()];
+    let arena = comrak::Arena::new();
+    let ast = comrak::parse_document(&arena, markdown, &options.extensions.comrak_options());
+    header.to_owned() + &markdown::strip_links(markdown, ast, options.marker)
+}
 
-        let mut seen_keywords: HashSet<String> = HashSet::new();
-        let mut seen_urls: HashSet<String> = HashSet::new();
+// TODO: returning the String is an additional allocation that is not necessary
+fn link_content(
+    keywords: &keyword::Keywords,
+    path: &Path,
+    content: &str,
+    options: &Options,
+    policy: &mut dyn LinkPolicy,
+) -> anyhow::Result<Update> {
+    // set up
+    let doc = document::Document::parse(content, path, &options.schema)?;
+    if !doc.front_matter.autolink {
+        return Ok(Update {
+            content: content.to_owned(),
+            links: vec![],
+        });
+    }
+    let comrak_options = options.extensions.comrak_options();
+    let arena = comrak::Arena::new();
+    let ast = comrak::parse_document(&arena, doc.document, &comrak_options);
+    // only used when a seed is given, to sample among equally relevant slugs
+    let mut rng = options.seed.map(StdRng::seed_from_u64);
+    // the markdown is parsed without its front matter, so its lines are offset
+    let line_offset = doc.header.matches('\n').count();
 
-        for candidate in candidates {
-            if is_already_seen(&candidate, &seen_keywords, &seen_urls)
-                || is_already_seen(&candidate, &added_keyword, &added_url)
-            {
-                continue;
+    let source_map = markdown::SourceMap::new(doc.document);
+
+    let mut links: Vec<Link> = Vec::new();
+    let mut splices: Vec<(usize, usize, String)> = Vec::new();
+
+    // pages the author already linked to are not linked again
+    let linked = linked_slugs(keywords, &markdown::link_urls(ast), path, &options.urls);
+    let source = relevance::Source::new(&doc.slug, path, &doc.terms(), doc.document);
+    let headings = markdown::heading_lines(ast);
+
+    policy.start(&doc);
+    markdown::add_links(
+        &arena,
+        ast,
+        &options.containers,
+        options.marker,
+        |paragraph, sourcepos| {
+            let mut candidates =
+                generate_candidates(keywords, paragraph, &source, &options.urls, rng.as_mut());
+            candidates.retain(|c| !linked.contains(&c.slug));
+            if !options.reformat {
+                // links that cannot be found verbatim in the source are dropped
+                candidates.retain(|c| {
+                    source_map
+                        .locate(sourcepos, &paragraph[..c.end()])
+                        .is_some()
+                });
             }
+            if candidates.is_empty() {
+                return vec![];
+            }
+            candidates.sort();
+
+            let line = sourcepos.start.line + line_offset;
+            let paragraph_info = Paragraph {
+                text: paragraph,
+                line,
+                section: headings.partition_point(|line| *line <= sourcepos.start.line),
+            };
+            let mut res = policy.select(&doc, &paragraph_info, &candidates);
 
-            match res.last() {
-                Some(c) if c.end() > candidate.start => continue, // skip overlaps
-                _ => {
-                    seen_urls.insert(candidate.url.clone());
-                    seen_keywords.insert(candidate.keyword.clone());
-                    res.push(candidate);
+            // a policy may only pick among the candidates, without overlaps
+            res.sort();
+            let mut end = 0;
+            res.retain(|c| {
+                if !candidates.contains(c) {
+                    log::warn!(
+                        "ignored the link of {} to {} the policy made up",
+                        c.keyword,
+                        c.slug
+                    );
+                    return false;
                 }
+                if c.start < end {
+                    log::warn!(
+                        "ignored the overlapping link of {} to {}",
+                        c.keyword,
+                        c.slug
+                    );
+                    return false;
+                }
+                end = c.end();
+                true
+            });
+
+            if !options.reformat {
+                res.retain(
+                    |c| match source_map.locate(sourcepos, &paragraph[..c.end()]) {
+                        Some(offset) => {
+                            splices.push((offset + c.start, c.length, c.url.clone()));
+                            true
+                        }
+                        None => false,
+                    },
+                );
             }
-        }
+            links.extend(res.iter().map(|c| Link {
+                line,
+                keyword: c.keyword.clone(),
+                slug: c.slug.clone(),
+                url: c.url.clone(),
+                relevance: (c.targets > 1).then_some(c.relevance),
+            }));
+            res.into_iter()
+                .map(|c| (c.start, c.length, c.url))
+                .collect()
+        },
+    );
+
+    // the front matter is copied verbatim, delimiters, comments and all
+    let front_matter = doc.header;
+    if !options.reformat {
+        return Ok(Update {
+            content: front_matter.to_owned()
+                + &markdown::splice_links(doc.document, splices, options.marker),
+            links,
+        });
+    }
+
+    let mut out = Vec::with_capacity(doc.document.len());
+    comrak::format_commonmark(ast, &comrak_options, &mut out)?;
+    let mut rendered = String::from_utf8(out)?;
+    // comrak always renders LF line endings
+    if front_matter.contains("\r\n") || doc.document.contains("\r\n") {
+        rendered = rendered.replace('\n', "\r\n");
+    }
+
+    Ok(Update {
+        content: front_matter.to_owned() + &rendered,
+        links,
+    })
+}
+
+/// Where the updated markdowns are written to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Destination {
+    /// a separate folder, preserving the folder structure of the glob
+    Directory(PathBuf),
+    /// the matched files themselves, optionally backing up the originals
+    InPlace(Option<Backup>),
+}
+
+/// How the original of a file rewritten in place is kept around.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Backup {
+    /// next to the original, with this suffix appended to its file name
+    Suffix(String),
+    /// in this folder, preserving the folder structure of the glob
+    Directory(PathBuf),
+}
 
-        res.shuffle(&mut rng);
-        if res.len() > 4 {
-            res.drain(4..);
+impl Backup {
+    fn path(&self, path: &Path) -> PathBuf {
+        match self {
+            Backup::Suffix(suffix) => {
+                let mut name = path.as_os_str().to_owned();
+                name.push(suffix);
+                PathBuf::from(name)
+            }
+            Backup::Directory(dir) => nested(dir, path),
         }
+    }
+}
 
-        added_url.extend(res.iter().map(|c| c.url.clone()));
-        added_keyword.extend(res.iter().map(|c| c.keyword.clone()));
-        res.into_iter().map(Candidate::tuple).collect()
-    });
+/// The path inside the folder, with any root, drive or `.` of the path left
+/// out, so that absolute globs are nested in the folder too.
+fn nested(dir: &Path, path: &Path) -> PathBuf {
+    let relative: PathBuf = path
+        .components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .collect();
+    dir.join(relative)
+}
+
+/// Writes the contents to a temporary file next to the path and then renames
+/// it over the path, so an interrupted run never leaves a truncated file.
+fn write_atomic(path: &Path, contents: &str) -> anyhow::Result<()> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    create_dir_all(parent)?;
 
-    let mut out = Vec::with_capacity(content.len());
-    write!(&mut out, "---\n{}\n---\n", doc.front_matter_all.trim(),)?;
-    comrak::format_commonmark(ast, &Default::default(), &mut out)?;
+    let Some(file_name) = path.file_name() else {
+        return Err(anyhow::anyhow!("{} is not a file", path.to_string_lossy()));
+    };
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(format!(".{}.tmp", std::process::id()));
+    let tmp_path = parent.join(tmp_name);
+
+    let result = (|| -> anyhow::Result<()> {
+        let mut out = fs::File::create(&tmp_path)?;
+        out.write_all(contents.as_bytes())?;
+        if let Ok(metadata) = fs::metadata(path) {
+            out.set_permissions(metadata.permissions())?;
+        }
+        out.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    })();
 
-    Ok(String::from_utf8(out)?)
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
 }
 
-pub fn write_glob(
+/// Runs `update_content` over every file in the glob, handing the path, the
+/// original content and the update to `f`. Files that cannot be parsed are
+/// passed through unchanged.
+fn update_glob<F>(
     keywords: &keyword::Keywords,
     glob_str: &str,
-    output: &Path,
-) -> anyhow::Result<()> {
+    options: &Options,
+    policy: &mut dyn LinkPolicy,
+    mut f: F,
+) -> anyhow::Result<()>
+where
+    F: FnMut(&Path, &str, &Update) -> anyhow::Result<()>,
+{
     // Glob for markdown files
     for entry in glob::glob(glob_str)? {
         if glob_str.contains("..") {
@@ -148,8 +448,6 @@ pub fn write_glob(
         }
         let path = entry?;
 
-        let output_path = output.join(&path);
-
         // Read the file content
         let mut file = fs::File::open(&path)?;
         let mut content = String::new();
@@ -157,27 +455,991 @@ pub fn write_glob(
 
         log::info!("updating {}", path.to_string_lossy());
 
-        let updated_file = match update_content(keywords, &content) {
-            Ok(file) => file,
+        let update = match update_content(keywords, &path, &content, options, policy) {
+            Ok(update) => update,
             Err(err) => match err.downcast_ref::<document::Error>() {
                 Some(doc_err) => {
                     log::warn!(
-                        "skipped updating {} since it's missing keywords or slugs: {}",
+                        "skipped updating {} since its front matter cannot be read: {}",
                         path.to_string_lossy(),
                         doc_err
                     );
-                    content
+                    Update {
+                        content: content.clone(),
+                        links: vec![],
+                    }
                 }
                 None => Err(err)?,
             },
         };
 
-        if let Some(parent) = output_path.parent() {
-            create_dir_all(parent)?;
+        f(&path, &content, &update)?;
+    }
+    Ok(())
+}
+
+pub fn write_glob(
+    keywords: &keyword::Keywords,
+    glob_str: &str,
+    destination: &Destination,
+    options: &Options,
+    policy: &mut dyn LinkPolicy,
+) -> anyhow::Result<()> {
+    update_glob(
+        keywords,
+        glob_str,
+        options,
+        policy,
+        |path, content, update| {
+            let updated_file = &update.content;
+            match destination {
+                Destination::Directory(output) => {
+                    write_atomic(&nested(output, path), updated_file)?
+                }
+                Destination::InPlace(_) if updated_file == content => (),
+                Destination::InPlace(backup) => {
+                    if let Some(backup) = backup {
+                        let backup_path = backup.path(path);
+                        if backup_path == path {
+                            return Err(anyhow::anyhow!(
+                                "cannot back up {} onto itself",
+                                path.to_string_lossy()
+                            ));
+                        }
+                        if let Some(parent) = backup_path.parent() {
+                            create_dir_all(parent)?;
+                        }
+                        fs::copy(path, &backup_path)?;
+                    }
+                    write_atomic(path, updated_file)?;
+                }
+            }
+            Ok(())
+        },
+    )
+}
+
+/// Returns the files in the glob that would change if they were updated,
+/// without writing anything.
+pub fn check_glob(
+    keywords: &keyword::Keywords,
+    glob_str: &str,
+    options: &Options,
+    policy: &mut dyn LinkPolicy,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let mut changed = Vec::new();
+    update_glob(
+        keywords,
+        glob_str,
+        options,
+        policy,
+        |path, content, update| {
+            if content != update.content {
+                changed.push(path.to_owned());
+            }
+            Ok(())
+        },
+    )?;
+    Ok(changed)
+}
+
+/// Prints a unified diff of every file in the glob that would change,
+/// without writing anything.
+pub fn diff_glob<W: Write>(
+    keywords: &keyword::Keywords,
+    glob_str: &str,
+    options: &Options,
+    policy: &mut dyn LinkPolicy,
+    out: &mut W,
+) -> anyhow::Result<()> {
+    update_glob(
+        keywords,
+        glob_str,
+        options,
+        policy,
+        |path, content, update| {
+            if content == update.content {
+                return Ok(());
+            }
+            let path = path.to_string_lossy();
+            let diff = similar::TextDiff::from_lines(content, &update.content);
+            write!(
+                out,
+                "{}",
+                diff.unified_diff()
+                    .header(&format!("a/{path}"), &format!("b/{path}"))
+            )?;
+            Ok(())
+        },
+    )
+}
+
+/// Prints the links that would be inserted into every file in the glob,
+/// without writing anything.
+pub fn dry_run_glob<W: Write>(
+    keywords: &keyword::Keywords,
+    glob_str: &str,
+    options: &Options,
+    policy: &mut dyn LinkPolicy,
+    out: &mut W,
+) -> anyhow::Result<()> {
+    update_glob(keywords, glob_str, options, policy, |path, _, update| {
+        if update.links.is_empty() {
+            return Ok(());
         }
+        writeln!(out, "{}", path.to_string_lossy())?;
+        for link in &update.links {
+            write!(out, "  {}: {} -> {}", link.line, link.keyword, link.slug)?;
+            match &link.relevance {
+                Some(relevance) => writeln!(out, ", relevance {relevance}")?,
+                None => writeln!(out)?,
+            }
+        }
+        Ok(())
+    })
+}
 
-        let mut out = fs::File::create(&output_path)?;
-        out.write_all(updated_file.as_bytes())?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget;
+    use crate::policy::DefaultPolicy;
+
+    fn run(
+        keywords: &keyword::Keywords,
+        path: &Path,
+        content: &str,
+        options: &Options,
+    ) -> anyhow::Result<Update> {
+        let mut policy = DefaultPolicy::new(Default::default(), options.seed);
+        update_content(keywords, path, content, options, &mut policy)
+    }
+
+    fn candidates(keywords: &keyword::Keywords, paragraph: &str) -> Vec<Candidate> {
+        let source = relevance::Source::new("source", Path::new("source.md"), &[], "");
+        let mut candidates =
+            generate_candidates::<StdRng>(keywords, paragraph, &source, &Default::default(), None);
+        candidates.sort();
+        candidates
+    }
+
+    #[test]
+    fn test_generate_candidates_single_word() {
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert("tokio-guide", &["tokio"]);
+
+        let result = candidates(&keywords, "we run on Tokio today");
+        assert_eq!(
+            result,
+            vec![Candidate {
+                start: 10,
+                length: 5,
+                keyword: "tokio".to_string(),
+                slug: "tokio-guide".to_string(),
+                url: "tokio-guide".to_string(),
+                relevance: Default::default(),
+                targets: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_generate_candidates_long_phrase() {
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert("pipeline", &["the continuous integration build pipeline"]);
+
+        let result = candidates(
+            &keywords,
+            "see the continuous integration build pipeline docs",
+        );
+        assert_eq!(
+            result,
+            vec![Candidate {
+                start: 4,
+                length: 41,
+                keyword: "the continuous integration build pipeline".to_string(),
+                slug: "pipeline".to_string(),
+                url: "pipeline".to_string(),
+                relevance: Default::default(),
+                targets: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_generate_candidates_punctuation() {
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert("front-matter", &["front matter"]);
+
+        for paragraph in [
+            "see the front matter.",
+            "see the (front matter)",
+            "see the front matter's fields",
+        ] {
+            let result = candidates(&keywords, paragraph);
+            assert_eq!(result.len(), 1, "{paragraph}");
+            assert_eq!(&paragraph[result[0].start..result[0].end()], "front matter");
+        }
+    }
+
+    #[test]
+    fn test_generate_candidates_skips_own_slug() {
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert("source", &["kubernetes"]);
+
+        assert!(candidates(&keywords, "deploy to kubernetes").is_empty());
+    }
+
+    #[test]
+    fn test_generate_candidates_picks_first_slug() {
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert("data-pipeline", &["pipeline"]);
+        keywords.insert("ci-pipeline", &["pipeline"]);
+
+        let result = candidates(&keywords, "the pipeline");
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].url, "ci-pipeline");
+    }
+
+    #[test]
+    fn test_update_content_prefers_longer_overlapping_phrase() {
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert("rust", &["async rust"]);
+        keywords.insert("runtime", &["rust runtime library"]);
+        keywords.insert("tokio", &["tokio"]);
+        let content = "---\nslug: source\n---\nasync rust runtime library tokio\n";
+
+        let update = run(
+            &keywords,
+            Path::new("source.md"),
+            content,
+            &Options::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            update.content,
+            "---\nslug: source\n---\nasync [rust runtime library](runtime) [tokio](tokio)\n"
+        );
+
+        // a repeated phrase does not crowd out another link
+        let content =
+            "---\nslug: source\n---\nrust runtime library and async rust runtime library\n";
+        let update = run(
+            &keywords,
+            Path::new("source.md"),
+            content,
+            &Options::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            update.content,
+            "---\nslug: source\n---\n[rust runtime library](runtime) and [async rust](rust) runtime library\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_candidates_picks_most_relevant_slug() {
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert("ci-pipeline", &["pipeline", "build"]);
+        keywords.insert("data-pipeline", &["pipeline", "warehouse"]);
+
+        let paragraph = "the warehouse pipeline";
+        let source = relevance::Source::new("source", Path::new("source.md"), &[], paragraph);
+        let result =
+            generate_candidates::<StdRng>(&keywords, paragraph, &source, &Default::default(), None);
+        let pipeline = result.iter().find(|c| c.keyword == "pipeline").unwrap();
+        assert_eq!(pipeline.slug, "data-pipeline");
+        assert_eq!(pipeline.relevance.keywords, 2);
+        assert_eq!(pipeline.targets, 2);
+    }
+
+    const CONTENT: &str = "---
+slug: source
+keywords: []
+---
+
+alpha beta gamma delta epsilon and more alpha beta gamma delta epsilon.
+";
+
+    fn greek_keywords() -> keyword::Keywords {
+        let mut keywords = keyword::Keywords::new();
+        for (i, word) in ["alpha", "beta", "gamma", "delta", "epsilon"]
+            .iter()
+            .enumerate()
+        {
+            keywords.insert(&format!("{word}-{}", i % 2), &[word]);
+            keywords.insert(&format!("{word}-{}", i % 2 + 2), &[word]);
+        }
+        keywords
+    }
+
+    #[test]
+    fn test_update_content_deterministic() {
+        let keywords = greek_keywords();
+        let first = run(
+            &keywords,
+            Path::new("source.md"),
+            CONTENT,
+            &Options::default(),
+        )
+        .unwrap()
+        .content;
+        for _ in 0..10 {
+            assert_eq!(
+                run(
+                    &keywords,
+                    Path::new("source.md"),
+                    CONTENT,
+                    &Options::default()
+                )
+                .unwrap()
+                .content,
+                first
+            );
+        }
+        // equally good links are kept in order of their url
+        assert!(first.contains("[beta](beta-1)"));
+        assert!(first.contains("[epsilon](epsilon-0)"));
+        assert!(!first.contains("[gamma]"));
+    }
+
+    #[test]
+    fn test_update_content_seeded() {
+        let keywords = greek_keywords();
+        let seeded = Options {
+            seed: Some(7),
+            ..Default::default()
+        };
+        let first = run(&keywords, Path::new("source.md"), CONTENT, &seeded)
+            .unwrap()
+            .content;
+        for _ in 0..10 {
+            assert_eq!(
+                run(&keywords, Path::new("source.md"), CONTENT, &seeded)
+                    .unwrap()
+                    .content,
+                first
+            );
+        }
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mdlinker-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_write_atomic() {
+        let dir = scratch_dir("write-atomic");
+        let path = dir.join("nested").join("doc.md");
+
+        write_atomic(&path, "first").unwrap();
+        write_atomic(&path, "second").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_backup_path() {
+        let path = Path::new("docs/guide.md");
+        assert_eq!(
+            Backup::Suffix(".bak".to_string()).path(path),
+            PathBuf::from("docs/guide.md.bak")
+        );
+        assert_eq!(
+            Backup::Directory(PathBuf::from("backups")).path(path),
+            PathBuf::from("backups/docs/guide.md")
+        );
+        assert_eq!(
+            Backup::Directory(PathBuf::from("/backups")).path(Path::new("/srv/./docs/guide.md")),
+            PathBuf::from("/backups/srv/docs/guide.md")
+        );
+    }
+
+    #[test]
+    fn test_write_glob_absolute_backup() {
+        let dir = scratch_dir("absolute-backup");
+        let path = dir.join("docs/guide.md");
+        let content = "---\nslug: guide\n---\nsee the setup\n";
+        write_atomic(&path, content).unwrap();
+        write_atomic(&dir.join("docs/setup.md"), "---\nslug: setup\n---\nsetup\n").unwrap();
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert("setup", &["setup"]);
+
+        let backups = dir.join("backups");
+        let glob_str = format!("{}/docs/*.md", dir.to_string_lossy());
+        assert!(Path::new(&glob_str).is_absolute());
+        let destination = Destination::InPlace(Some(Backup::Directory(backups.clone())));
+        write_glob(
+            &keywords,
+            &glob_str,
+            &destination,
+            &Options::default(),
+            &mut DefaultPolicy::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(nested(&backups, &path)).unwrap(),
+            content
+        );
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "---\nslug: guide\n---\nsee the [setup](setup)\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_check_glob() {
+        let dir = scratch_dir("check-glob");
+        fs::write(
+            dir.join("linked.md"),
+            "---\nslug: linked\nkeywords: [tokio]\n---\nplain text\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("unlinked.md"),
+            "---\nslug: unlinked\nkeywords: []\n---\nwe use tokio\n",
+        )
+        .unwrap();
+        let glob_str = format!("{}/*.md", dir.to_string_lossy());
+
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert("linked", &["tokio"]);
+
+        let changed = check_glob(
+            &keywords,
+            &glob_str,
+            &Options::default(),
+            &mut DefaultPolicy::default(),
+        )
+        .unwrap();
+        assert_eq!(changed, vec![dir.join("unlinked.md")]);
+        assert_eq!(
+            fs::read_to_string(dir.join("unlinked.md")).unwrap(),
+            "---\nslug: unlinked\nkeywords: []\n---\nwe use tokio\n"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_update_content_links() {
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert("tokio-guide", &["tokio"]);
+        keywords.insert("k8s", &["kubernetes"]);
+        let content =
+            "---\nslug: source\nkeywords: []\n---\n\n# Title\n\nwe run\nkubernetes and tokio\n";
+
+        let update = run(
+            &keywords,
+            Path::new("source.md"),
+            content,
+            &Options::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            update.links,
+            vec![
+                Link {
+                    line: 9,
+                    keyword: "kubernetes".to_string(),
+                    slug: "k8s".to_string(),
+                    url: "k8s".to_string(),
+                    relevance: None,
+                },
+                Link {
+                    line: 9,
+                    keyword: "tokio".to_string(),
+                    slug: "tokio-guide".to_string(),
+                    url: "tokio-guide".to_string(),
+                    relevance: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_and_dry_run_glob() {
+        let dir = scratch_dir("diff-glob");
+        let content = "---\nslug: source\nkeywords: []\n---\nwe use tokio\n";
+        fs::write(dir.join("doc.md"), content).unwrap();
+        let glob_str = format!("{}/*.md", dir.to_string_lossy());
+        let path = dir.join("doc.md").to_string_lossy().into_owned();
+
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert("tokio-guide", &["tokio"]);
+
+        let mut out = Vec::new();
+        diff_glob(
+            &keywords,
+            &glob_str,
+            &Options::default(),
+            &mut DefaultPolicy::default(),
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "--- a/{path}\n+++ b/{path}\n@@ -2,4 +2,4 @@\n slug: source\n keywords: []\n ---\n-we use tokio\n+we use [tokio](tokio-guide)\n"
+            )
+        );
+
+        let mut out = Vec::new();
+        dry_run_glob(
+            &keywords,
+            &glob_str,
+            &Options::default(),
+            &mut DefaultPolicy::default(),
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("{path}\n  5: tokio -> tokio-guide\n")
+        );
+
+        assert_eq!(fs::read_to_string(dir.join("doc.md")).unwrap(), content);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_update_content_preserves_formatting() {
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert("tokio-guide", &["tokio"]);
+        let content = "---\nslug:   source\nkeywords: []\n---\n\nTitle\n=====\n\n* a _list_\n* b\n\nwe   run \\*tokio\\* and\n    tokio again\n";
+
+        let update = run(
+            &keywords,
+            Path::new("source.md"),
+            content,
+            &Options::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            update.content,
+            "---\nslug:   source\nkeywords: []\n---\n\nTitle\n=====\n\n* a _list_\n* b\n\nwe   run \\*tokio\\* and\n    [tokio](tokio-guide) again\n"
+        );
+    }
+
+    #[test]
+    fn test_update_content_url_template() {
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert("tokio-guide", &["tokio"]);
+        keywords.set_path("tokio-guide", Path::new("docs/rust/tokio.md"));
+        let content = "---\nslug: source\nkeywords: []\n---\nwe use tokio\n";
+        let path = Path::new("docs/guides/source.md");
+
+        let options = Options {
+            urls: url::UrlTemplate::new("{base}/{slug}/", "https://example.com").unwrap(),
+            ..Default::default()
+        };
+        let update = run(&keywords, path, content, &options).unwrap();
+        assert!(update
+            .content
+            .ends_with("we use [tokio](https://example.com/tokio-guide/)\n"));
+
+        let options = Options {
+            urls: url::UrlTemplate::new("{relstem}.html", "").unwrap(),
+            ..Default::default()
+        };
+        let update = run(&keywords, path, content, &options).unwrap();
+        assert!(update
+            .content
+            .ends_with("we use [tokio](../rust/tokio.html)\n"));
+    }
+
+    #[test]
+    fn test_update_content_nested_paragraphs() {
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert("tokio-guide", &["tokio"]);
+        keywords.insert("k8s", &["kubernetes"]);
+        let content =
+            "---\nslug: source\nkeywords: []\n---\n- runs on\n  kubernetes\n\n  > with *tokio*\n";
+
+        let update = run(
+            &keywords,
+            Path::new("source.md"),
+            content,
+            &Options::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            update.content,
+            "---\nslug: source\nkeywords: []\n---\n- runs on\n  [kubernetes](k8s)\n\n  > with *tokio*\n"
+        );
+
+        let options = Options {
+            containers: [
+                markdown::Container::List,
+                markdown::Container::BlockQuote,
+                markdown::Container::Emph,
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let update = run(&keywords, Path::new("source.md"), content, &options).unwrap();
+        assert_eq!(
+            update.content,
+            "---\nslug: source\nkeywords: []\n---\n- runs on\n  [kubernetes](k8s)\n\n  > with *[tokio](tokio-guide)*\n"
+        );
+
+        // without emph the quote is reached, but not the text inside the emphasis
+        let options = Options {
+            containers: [markdown::Container::List, markdown::Container::BlockQuote]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let update = run(&keywords, Path::new("source.md"), content, &options).unwrap();
+        assert_eq!(update.links.len(), 1);
+    }
+
+    #[test]
+    fn test_update_content_gfm_table() {
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert("tokio-guide", &["tokio"]);
+        let content = "---\nslug: source\nkeywords: []\n---\n| runtime | notes |\n|:--|--:|\n| tokio | fast |\n";
+
+        let options = Options {
+            extensions: [markdown::Extension::Gfm].into_iter().collect(),
+            ..Default::default()
+        };
+        let update = run(&keywords, Path::new("source.md"), content, &options).unwrap();
+        assert_eq!(
+            update.content,
+            "---\nslug: source\nkeywords: []\n---\n| runtime | notes |\n|:--|--:|\n| [tokio](tokio-guide) | fast |\n"
+        );
+
+        let options = Options {
+            reformat: true,
+            ..options
+        };
+        let update = run(&keywords, Path::new("source.md"), content, &options).unwrap();
+        assert!(update.content.contains("| [tokio](tokio-guide) | fast |"));
+    }
+
+    #[test]
+    fn test_update_content_reformat_keeps_dialect() {
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert("tokio-guide", &["tokio"]);
+        let options = Options {
+            reformat: true,
+            ..Default::default()
+        };
+
+        for (content, expected) in [
+            (
+                "---\nslug: source\nkeywords: []\n---\nwe use tokio\n",
+                "---\nslug: source\nkeywords: []\n---\nwe use [tokio](tokio-guide)\n",
+            ),
+            (
+                "+++\nslug = \"source\"\nkeywords = []\n+++\nwe use tokio\n",
+                "+++\nslug = \"source\"\nkeywords = []\n+++\nwe use [tokio](tokio-guide)\n",
+            ),
+            (
+                "{\"slug\": \"source\", \"keywords\": []}\nwe use tokio\n",
+                "{\"slug\": \"source\", \"keywords\": []}\nwe use [tokio](tokio-guide)\n",
+            ),
+        ] {
+            let update = run(&keywords, Path::new("source.md"), content, &options).unwrap();
+            assert_eq!(update.content, expected);
+        }
+    }
+
+    #[test]
+    fn test_update_content_reformat_keeps_front_matter() {
+        let keywords = keyword::Keywords::new();
+        let options = Options {
+            reformat: true,
+            ..Default::default()
+        };
+        let content = "\n---  \n\n# a comment\nslug: source   \nkeywords: []\n\n---\n\n* text\n";
+
+        let update = run(&keywords, Path::new("source.md"), content, &options).unwrap();
+        assert_eq!(
+            update.content,
+            "\n---  \n\n# a comment\nslug: source   \nkeywords: []\n\n---\n- text\n"
+        );
+    }
+
+    #[test]
+    fn test_update_content_crlf_and_bom() {
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert("tokio-guide", &["tokio"]);
+        let content = "\u{feff}---\r\nslug: source\r\nkeywords: []\r\n---\r\nwe use\r\ntokio\r\n";
+
+        let update = run(
+            &keywords,
+            Path::new("source.md"),
+            content,
+            &Options::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            update.content,
+            "\u{feff}---\r\nslug: source\r\nkeywords: []\r\n---\r\nwe use\r\n[tokio](tokio-guide)\r\n"
+        );
+
+        let options = Options {
+            reformat: true,
+            ..Default::default()
+        };
+        let update = run(&keywords, Path::new("source.md"), content, &options).unwrap();
+        assert_eq!(
+            update.content,
+            "\u{feff}---\r\nslug: source\r\nkeywords: []\r\n---\r\nwe use\r\n[tokio](tokio-guide)\r\n"
+        );
+    }
+
+    #[test]
+    fn test_linked_slugs() {
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert("guide", &["guide"]);
+        keywords.insert("reference/api", &["api"]);
+        keywords.insert("setup", &["setup"]);
+        keywords.set_path("setup", Path::new("docs/setup.md"));
+        keywords.insert("install", &["install"]);
+        keywords.set_path("install", Path::new("docs/howto/installing.md"));
+        let urls = url::UrlTemplate::new("{relstem}.html", "https://example.com").unwrap();
+
+        let link_urls = [
+            "setup.html",
+            "https://example.com/docs/guide/#usage",
+            "../reference/api.md?plain=1",
+            "./howto/installing.md",
+            "unrelated-guide",
+            "https://github.com/org/setup",
+            "mailto:setup",
+        ]
+        .map(String::from);
+        let linked = linked_slugs(&keywords, &link_urls, Path::new("docs/a.md"), &urls);
+        assert_eq!(
+            linked,
+            HashSet::from(["setup", "guide", "reference/api", "install"].map(String::from))
+        );
+    }
+
+    #[test]
+    fn test_update_content_skips_manually_linked() {
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert("front-matter-guide", &["front matter"]);
+        keywords.insert("tokio-guide", &["tokio"]);
+        let content = "---\nslug: source\nkeywords: []\n---\nSee [the front matter](front-matter-guide).\n\nfront matter and tokio\n";
+
+        let update = run(
+            &keywords,
+            Path::new("source.md"),
+            content,
+            &Options::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            update.content,
+            "---\nslug: source\nkeywords: []\n---\nSee [the front matter](front-matter-guide).\n\nfront matter and [tokio](tokio-guide)\n"
+        );
+    }
+
+    #[test]
+    fn test_update_content_rerun_replaces_marked_links() {
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert("tokio-guide", &["tokio"]);
+        keywords.insert("async-guide", &["async rust"]);
+        let content = "---\nslug: source\n---\nasync rust with tokio and [serde](serde)\n";
+
+        for reformat in [false, true] {
+            let options = Options {
+                marker: markdown::Marker::Title,
+                reformat,
+                ..Default::default()
+            };
+            let linked = "---\nslug: source\n---\n[async rust](async-guide \"mdlinker\") with [tokio](tokio-guide \"mdlinker\") and [serde](serde)\n";
+            let first = run(&keywords, Path::new("source.md"), content, &options).unwrap();
+            assert_eq!(first.content, linked);
+            let second = run(&keywords, Path::new("source.md"), &first.content, &options).unwrap();
+            assert_eq!(second, first);
+
+            // a keyword that went away takes its link with it
+            let mut fewer = keyword::Keywords::new();
+            fewer.insert("tokio-guide", &["tokio"]);
+            let third = run(&fewer, Path::new("source.md"), linked, &options).unwrap();
+            assert_eq!(
+                third.content,
+                "---\nslug: source\n---\nasync rust with [tokio](tokio-guide \"mdlinker\") and [serde](serde)\n"
+            );
+
+            let options = Options {
+                unlink: true,
+                ..options
+            };
+            let unlinked = run(&keywords, Path::new("source.md"), linked, &options).unwrap();
+            assert_eq!(unlinked.content, content);
+            assert!(unlinked.links.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_update_content_autolink_false() {
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert("tokio-guide", &["tokio"]);
+        let content = "---\nslug: source\nautolink: false\n---\nwe use tokio\n";
+
+        let update = run(
+            &keywords,
+            Path::new("source.md"),
+            content,
+            &Options::default(),
+        )
+        .unwrap();
+        assert_eq!(update.content, content);
+        assert!(update.links.is_empty());
+    }
+
+    #[test]
+    fn test_update_content_budget() {
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert("tokio-guide", &["tokio"]);
+        keywords.insert("serde-guide", &["serde"]);
+        let body = "tokio and serde\n\n# Next\n\ntokio again and serde\n";
+        let content = format!("---\nslug: source\n---\n{body}");
+        let linked = |budget: budget::Budget, content: &str| {
+            let mut policy = DefaultPolicy::new(budget, None);
+            let update = update_content(
+                &keywords,
+                Path::new("source.md"),
+                content,
+                &Default::default(),
+                &mut policy,
+            );
+            let links = update.unwrap().links;
+            links
+                .into_iter()
+                .map(|link| (link.line, link.keyword))
+                .collect::<Vec<_>>()
+        };
+        let pairs = |pairs: &[(usize, &str)]| {
+            pairs
+                .iter()
+                .map(|(line, keyword)| (*line, keyword.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            linked(Default::default(), &content),
+            pairs(&[(4, "tokio"), (4, "serde")])
+        );
+        let relink = budget::Budget {
+            relink_sections: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            linked(relink, &content),
+            pairs(&[(4, "tokio"), (4, "serde"), (8, "tokio"), (8, "serde")])
+        );
+        let per_document = budget::Budget {
+            per_document: 3,
+            ..relink
+        };
+        assert_eq!(
+            linked(per_document, &content),
+            pairs(&[(4, "tokio"), (4, "serde"), (8, "serde")])
+        );
+        let gap = budget::Budget {
+            min_word_gap: 2,
+            ..Default::default()
+        };
+        assert_eq!(linked(gap, &content), pairs(&[(4, "serde"), (8, "tokio")]));
+
+        // the front matter overrides the options
+        let content = format!("---\nslug: source\nmax_links_per_paragraph: 1\n---\n{body}");
+        assert_eq!(
+            linked(relink, &content),
+            pairs(&[(5, "serde"), (9, "serde")])
+        );
+    }
+
+    /// links every occurrence, overlapping or not
+    struct EveryOccurrence {
+        lines: Vec<usize>,
+    }
+
+    impl LinkPolicy for EveryOccurrence {
+        fn select(
+            &mut self,
+            _doc: &document::Document,
+            paragraph: &Paragraph,
+            candidates: &[Candidate],
+        ) -> Vec<Candidate> {
+            self.lines.push(paragraph.line);
+            candidates.to_vec()
+        }
+    }
+
+    #[test]
+    fn test_update_content_custom_policy() {
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert("tokio-guide", &["tokio"]);
+        keywords.insert("async-guide", &["async rust"]);
+        keywords.insert("rust-guide", &["rust"]);
+        let content = "---\nslug: source\n---\ntokio is async rust, tokio\n";
+
+        let mut policy = EveryOccurrence { lines: vec![] };
+        let update = update_content(
+            &keywords,
+            Path::new("source.md"),
+            content,
+            &Default::default(),
+            &mut policy,
+        )
+        .unwrap();
+        // the overlapping link to rust-guide is left out
+        assert_eq!(
+            update.content,
+            "---\nslug: source\n---\n[tokio](tokio-guide) is [async rust](async-guide), [tokio](tokio-guide)\n"
+        );
+        assert_eq!(policy.lines, vec![4]);
+    }
+
+    /// links a made up occurrence that is not a keyword
+    struct MadeUp;
+
+    impl LinkPolicy for MadeUp {
+        fn select(
+            &mut self,
+            _doc: &document::Document,
+            paragraph: &Paragraph,
+            candidates: &[Candidate],
+        ) -> Vec<Candidate> {
+            let mut made_up = candidates[0].clone();
+            // in the middle of the ü
+            made_up.start = paragraph.text.find('ü').unwrap() + 1;
+            vec![made_up, candidates[0].clone()]
+        }
+    }
+
+    #[test]
+    fn test_update_content_policy_made_up_candidate() {
+        let mut keywords = keyword::Keywords::new();
+        keywords.insert("tokio-guide", &["tokio"]);
+        let content = "---\nslug: source\n---\ntokio ünïcode\n";
+
+        let update = update_content(
+            &keywords,
+            Path::new("source.md"),
+            content,
+            &Default::default(),
+            &mut MadeUp,
+        )
+        .unwrap();
+        assert_eq!(
+            update.content,
+            "---\nslug: source\n---\n[tokio](tokio-guide) ünïcode\n"
+        );
     }
-    Ok(())
 }